@@ -0,0 +1,54 @@
+use criterion::{criterion_group, criterion_main, Criterion, Throughput, BatchSize};
+use inkbound_parser::{parser::{LogParser, DataLog}, generator::{LogGenerator, GeneratorOptions}};
+
+/// Build a synthetic multi-megabyte log, roughly shaped like a full dive
+fn synthetic_log() -> String {
+    let options = GeneratorOptions::default()
+        .seed(0)
+        .combats(10)
+        .turns(12)
+        .hits(6)
+        .status_effects(vec!["Burn".to_string()])
+        .status_rate(1.0)
+        .orb_rate(1.0)
+        .noise(12);
+    LogGenerator::new(options).generate()
+}
+
+fn parse_benchmark(c: &mut Criterion) {
+    let log = synthetic_log();
+    let lines: Vec<&str> = log.split('\n').collect();
+
+    let mut group = c.benchmark_group("lines");
+    group.throughput(Throughput::Elements(lines.len() as u64));
+    group.bench_function("parse_lines", |b| b.iter_batched(
+        LogParser::new,
+        |mut parser| parser.parse_lines(&lines),
+        BatchSize::SmallInput,
+    ));
+    group.bench_function("parse_to_datalog", |b| b.iter_batched(
+        || (LogParser::new(), DataLog::new()),
+        |(mut parser, mut datalog)| {
+            datalog.handle_events(parser.parse_lines(&lines));
+            datalog
+        },
+        BatchSize::SmallInput,
+    ));
+    group.finish();
+
+    let mut group = c.benchmark_group("bytes");
+    group.throughput(Throughput::Bytes(log.len() as u64));
+    group.bench_function("parse_lines", |b| b.iter_batched(
+        LogParser::new,
+        |mut parser| parser.parse_lines(&lines),
+        BatchSize::SmallInput,
+    ));
+    group.finish();
+}
+
+criterion_group!{
+    name = benches;
+    config = Criterion::default().sample_size(20);
+    targets = parse_benchmark
+}
+criterion_main!(benches);
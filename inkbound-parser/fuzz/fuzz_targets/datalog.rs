@@ -0,0 +1,16 @@
+//! Run with `cargo +nightly fuzz run datalog` from the inkbound-parser directory.
+//! Feeds everything the parser emits through a DataLog, and serializes it the same way the overlay and --parse do.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use inkbound_parser::parser::{LogParser, DataLog};
+
+fuzz_target!(|data: &[u8]| {
+    let text = String::from_utf8_lossy(data);
+    let lines: Vec<&str> = text.split('\n').collect();
+
+    let mut parser = LogParser::new();
+    let mut datalog = DataLog::new();
+    datalog.handle_events(parser.parse_lines(&lines));
+    let _ = serde_json::to_string(&datalog);
+});
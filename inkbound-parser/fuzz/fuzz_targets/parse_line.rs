@@ -0,0 +1,14 @@
+//! Run with `cargo +nightly fuzz run parse_line` from the inkbound-parser directory.
+//! Seeding the corpus with a few lines from a real (sanitized) log helps it reach the capture patterns quickly.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use inkbound_parser::parser::LogParser;
+
+fuzz_target!(|data: &[u8]| {
+    let text = String::from_utf8_lossy(data);
+    let mut parser = LogParser::new();
+    for line in text.split('\n') {
+        parser.parse_line(line);
+    }
+});
@@ -1,6 +1,28 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    sync::{Arc, RwLock, atomic::{AtomicU64, Ordering}},
+};
+
 use serde::{Serialize, Deserialize};
 use strum::EnumIter;
 
+static BUNDLED_ASPECTS: &str = include_str!("aspects.ron");
+
+lazy_static::lazy_static! {
+    static ref REGISTRY: RwLock<Arc<AspectRegistry>> = RwLock::new(Arc::new(
+        AspectRegistry::from_ron(BUNDLED_ASPECTS).expect("bundled aspects failed to load")
+    ));
+}
+
+/// Bumped whenever the global registry changes, so each thread knows to refresh its copy
+static GENERATION: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+    /// This thread's copy of the global registry and its generation, so lookups (e.g. per row, per frame) don't take the lock
+    static CACHED: RefCell<Option<(u64, Arc<AspectRegistry>)>> = const { RefCell::new(None) };
+}
+
 // TODO: Consider manually implementing (De)Serialize
 //  May be more future proofed to not use a named string in a logfile,
 //  and letting clients handle sorting that part out.
@@ -14,22 +36,18 @@ pub enum Aspect {
     StarCaptain,
     Chainbreaker,
     Godkeeper,
-    // Possibly not yet implemented
+    // Possibly not yet implemented, or only known from a registry file
     Unknown(String),
 }
 
 impl std::fmt::Display for Aspect {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_str(match self {
-            Aspect::MagmaMiner => "Magma Miner",
-            Aspect::Mosscloak => "Mosscloak",
-            Aspect::Clairvoyant => "Clairvoyent",
-            Aspect::Weaver => "Weaver",
-            Aspect::Obelisk => "Obelisk",
-            Aspect::StarCaptain => "Star Captain",
-            Aspect::Chainbreaker => "Chainbreaker",
-            Aspect::Godkeeper => "Godkeeper",
-            Aspect::Unknown(id) => id.as_str(),
+        with_registry(|registry| match registry.get(self) {
+            Some(info) => f.write_str(&info.name),
+            None => match self {
+                Aspect::Unknown(id) => f.write_str(id),
+                known => write!(f, "{known:?}"),
+            },
         })
     }
 }
@@ -37,17 +55,181 @@ impl std::fmt::Display for Aspect {
 impl Aspect {
     /// Get an Aspect enum from the internal string
     pub fn from_id(id: &str) -> Self {
-        match id {
-            "C01" => Aspect::MagmaMiner,
-            "C02" => Aspect::Mosscloak,
-            "C03" => Aspect::Clairvoyant,
-            "C04" => Aspect::Weaver,
-            "C05" => Aspect::Obelisk,
-            "C06" => Aspect::Unknown("C06".to_string()),
-            "C07" => Aspect::StarCaptain,
-            "C08" => Aspect::Chainbreaker,
-            "C09" => Aspect::Godkeeper,
-            _ => Aspect::Unknown(id.to_string()),
+        with_registry(|registry| registry.by_id(id).map(AspectInfo::aspect))
+            .unwrap_or_else(|| Aspect::Unknown(id.to_string()))
+    }
+
+    /// Get an abbreviated name for the aspect
+    pub fn abbv(&self) -> String {
+        with_registry(|registry| registry.get(self).map(|info| info.abbv.clone()))
+            .unwrap_or_else(|| "UNK".to_string())
+    }
+
+    /// Default color for the aspect as rgb, if it has one
+    pub fn default_rgb(&self) -> Option<(u8, u8, u8)> {
+        with_registry(|registry| registry.get(self).map(|info| info.color))
+    }
+}
+
+#[derive(Debug)]
+pub enum AspectRegistryError {
+    /// Aspect file could not be read
+    Io(String, std::io::Error),
+    /// Aspect file is not valid
+    Parse(ron::error::SpannedError),
+}
+
+impl std::fmt::Display for AspectRegistryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AspectRegistryError::Io(path, e) => write!(f, "unable to read aspect file {path}: {e}"),
+            AspectRegistryError::Parse(e) => write!(f, "invalid aspect file: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for AspectRegistryError {}
+
+/// Metadata for a single aspect
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AspectInfo {
+    /// Internal class id, e.g. "C01"
+    pub id: String,
+    /// Built in variant this id maps to, if any
+    #[serde(default)]
+    pub aspect: Option<Aspect>,
+    pub name: String,
+    pub abbv: String,
+    pub color: (u8, u8, u8),
+}
+
+impl AspectInfo {
+    /// The Aspect reported for this id
+    pub fn aspect(&self) -> Aspect {
+        self.aspect.clone().unwrap_or_else(|| Aspect::Unknown(self.id.clone()))
+    }
+}
+
+/// All known aspects and their metadata, in display order
+#[derive(Debug, Clone, Default)]
+pub struct AspectRegistry {
+    aspects: Vec<AspectInfo>,
+    /// Positions in `aspects` by id, and by the Aspect reported for each. The first entry for an Aspect wins
+    by_id: HashMap<String, usize>,
+    by_aspect: HashMap<Aspect, usize>,
+}
+
+impl AspectRegistry {
+    /// Parse the contents of an aspect file
+    pub fn from_ron(data: &str) -> Result<Self, AspectRegistryError> {
+        let aspects = ron::from_str(data).map_err(AspectRegistryError::Parse)?;
+        let mut registry = Self { aspects, ..Default::default() };
+        registry.index();
+        Ok(registry)
+    }
+
+    fn index(&mut self) {
+        self.by_id.clear();
+        self.by_aspect.clear();
+        for (index, info) in self.aspects.iter().enumerate() {
+            self.by_id.entry(info.id.clone()).or_insert(index);
+            self.by_aspect.entry(info.aspect()).or_insert(index);
+        }
+    }
+
+    /// Load an aspect file
+    pub fn from_file(path: &str) -> Result<Self, AspectRegistryError> {
+        let data = std::fs::read_to_string(path).map_err(|e| AspectRegistryError::Io(path.to_string(), e))?;
+        Self::from_ron(&data)
+    }
+
+    /// Add aspects from another registry, replacing any with the same id
+    pub fn extend(&mut self, other: AspectRegistry) {
+        for info in other.aspects {
+            match self.by_id.get(&info.id) {
+                Some(&index) => self.aspects[index] = info,
+                None => self.aspects.push(info),
+            }
+            self.index();
         }
     }
+
+    pub fn by_id(&self, id: &str) -> Option<&AspectInfo> {
+        self.by_id.get(id).map(|&index| &self.aspects[index])
+    }
+
+    pub fn get(&self, aspect: &Aspect) -> Option<&AspectInfo> {
+        self.by_aspect.get(aspect).map(|&index| &self.aspects[index])
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &AspectInfo> {
+        self.aspects.iter()
+    }
+}
+
+/// This thread's copy of the global registry, refreshed first if the registry has changed
+fn cached_registry(cached: &mut Option<(u64, Arc<AspectRegistry>)>) -> &AspectRegistry {
+    let generation = GENERATION.load(Ordering::Acquire);
+    if cached.as_ref().is_none_or(|(cached_generation, _)| *cached_generation != generation) {
+        *cached = Some((generation, REGISTRY.read().unwrap().clone()));
+    }
+    &cached.as_ref().unwrap().1
+}
+
+/// Look something up in the global registry
+fn with_registry<T>(f: impl FnOnce(&AspectRegistry) -> T) -> T {
+    CACHED.with(|cached| f(cached_registry(&mut cached.borrow_mut())))
+}
+
+/// The global aspect registry, the bundled aspects plus any loaded with `load_aspects`
+pub fn registry() -> Arc<AspectRegistry> {
+    CACHED.with(|cached| {
+        let mut cached = cached.borrow_mut();
+        cached_registry(&mut cached);
+        cached.as_ref().unwrap().1.clone()
+    })
+}
+
+/// Load an aspect file into the global registry, adding to or overriding the bundled aspects
+pub fn load_aspects(path: &str) -> Result<(), AspectRegistryError> {
+    let aspects = AspectRegistry::from_file(path)?;
+    let mut registry = REGISTRY.write().unwrap();
+    let mut extended = AspectRegistry::clone(&registry);
+    extended.extend(aspects);
+    *registry = Arc::new(extended);
+    GENERATION.fetch_add(1, Ordering::Release);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bundled_aspects() {
+        assert_eq!(Aspect::from_id("C03"), Aspect::Clairvoyant);
+        assert_eq!(Aspect::from_id("C06"), Aspect::Unknown("C06".to_string()));
+        assert_eq!(Aspect::Clairvoyant.to_string(), "Clairvoyant");
+        assert_eq!(Aspect::StarCaptain.abbv(), "STC");
+        assert_eq!(Aspect::Unknown("C06".to_string()).to_string(), "C06");
+        assert_eq!(Aspect::Unknown("C06".to_string()).abbv(), "UNK");
+    }
+
+    #[test]
+    fn extend_registry() {
+        let mut registry = AspectRegistry::from_ron(BUNDLED_ASPECTS).unwrap();
+        let extra = AspectRegistry::from_ron(r#"#![enable(implicit_some)]
+        [
+            (id: "C01", aspect: MagmaMiner, name: "Magma Miner", abbv: "MAG", color: (1, 2, 3)),
+            (id: "C10", name: "New Aspect", abbv: "NEW", color: (4, 5, 6)),
+        ]"#).unwrap();
+        registry.extend(extra);
+
+        assert_eq!(registry.get(&Aspect::MagmaMiner).unwrap().abbv, "MAG");
+        let new = registry.by_id("C10").unwrap();
+        assert_eq!(new.aspect(), Aspect::Unknown("C10".to_string()));
+        assert_eq!(registry.get(&Aspect::Unknown("C10".to_string())).unwrap().name, "New Aspect");
+        assert_eq!(registry.iter().count(), 9);
+    }
 }
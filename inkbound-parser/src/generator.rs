@@ -0,0 +1,399 @@
+use rand::{Rng, SeedableRng, rngs::StdRng, seq::SliceRandom};
+
+use crate::aspects::{self, Aspect};
+
+// Lines the parser is interested in, with {placeholders} filled in per line
+static JOIN_HUB: &str = "Joining hub - characterId: {character}, characterName: {name}, partyId: {party}";
+static START_DIVE: &str = "Party run start triggered - solo party: {solo}";
+static UNIT_CLASS: &str = "Setting unit class for animation-UnitEntityHandle:(EntityHandle:{source})-classType:{class}";
+static REGISTER_NAME: &str = "{name} (EntityHandle:{source}) is playing ability AbilityData-{skill}_AbilityData ({skill} my7gMbFo)";
+static START_COMBAT: &str = "[EventSystem] broadcasting EventOnCombatStarted-WorldStateChangeCombatStarted-CombatZoneHandle:(EntityHandle:{zone})-TriggeringInteractableHandle:(EntityHandle:{trigger})";
+static NEXT_TURN: &str = "Evaluating quest progress for (EntityHandle:{source}) with 101 active quests. Record variable: QuestObjective_TurnCount";
+static DAMAGE: &str = "[EventSystem] broadcasting EventOnUnitDamaged-WorldStateChangeDamageUnit-TargetUnitHandle:(EntityHandle:{target})-SourceEntityHandle:(EntityHandle:{source})-TargetUnitTeam:{team}-IsInActiveCombat:True-DamageAmount:{amount}-IsCriticalHit:{crit}-WasDodged:{dodged}-ActionData:ActionData-{skill}_Action (UPNE5APs)-AbilityData:AbilityData-{skill}_AbilityData ({skill} my7gMbFo)-StatusEffectData:(none)-LootableData:(none)";
+static ADD_STATUS: &str = "[EventSystem] broadcasting EventOnUnitStatusEffectStacksAdded-WorldStateChangeUnitAddStatusEffectStacks-TargetUnitEntityHandle:(EntityHandle:{target})-CasterUnitEntityHandle:(EntityHandle:{source})-TargetUnitTeam:Enemy-IsInActiveCombat:True-StatusEffectInstanceHandle:(Handle:3372)-StatusEffectData:StatusEffectData-{effect}_StatusEffect (HelperData_titleKey-vdrSrrVG-f73d28c6d6a09c44e9b41ad2b3704826 sXmQNYjg)-StacksAdded:{added}-NewStacksValue:{total}";
+static ORB_PICKUP: &str = "[EventSystem] broadcasting EventOnPickupActivated-WorldStateChangePickupActivated-PlayerUnitHandle:(EntityHandle:{source})-PickupHandle:(EntityHandle:95)-PickupData:PickupData-ManaOrbPickup (PickupData_pickupName-taadPy97-ccebe8a3bf921d043ac03a49bce8019f LzTNf24V)";
+static END_COMBAT: &str = "[EventSystem] broadcasting EventOnCombatEndSequenceStarted-WorldStateChangeCombatFinishedStartSequence";
+static END_DIVE: &str = "[EventSystem] broadcasting EventSetGameState-EndRun";
+
+// Filler lines the parser should ignore, which make up the bulk of a real log
+static NOISE: [&str; 4] = [
+    "[EventSystem] broadcasting EventOnUnitMoved-WorldStateChangeMoveUnit-UnitHandle:(EntityHandle:{target})-FromPosition:(3, 4)-ToPosition:(4, 4)",
+    "[AnimationController] Playing animation Idle_Combat on (EntityHandle:{target})",
+    "[EventSystem] broadcasting EventOnUnitStatusEffectStacksRemoved-WorldStateChangeUnitRemoveStatusEffectStacks-TargetUnitEntityHandle:(EntityHandle:{target})-StacksRemoved:1",
+    "[NetworkClient] Received message WorldStateChangeBatch (seq 48213, 12 changes)",
+];
+
+/// Entity handles of the first player and enemy, later ones count up from these
+const FIRST_PLAYER_HANDLE: i64 = 20;
+const FIRST_ENEMY_HANDLE: i64 = 1000;
+
+/// Aspects of the default party, named Player1 to Player4
+pub const DEFAULT_PARTY: [Aspect; 4] = [Aspect::MagmaMiner, Aspect::Weaver, Aspect::StarCaptain, Aspect::Obelisk];
+
+/// A chance that can be passed to gen_bool, which panics on NaN or anything outside of 0.0 - 1.0
+fn valid_rate(rate: f64) -> f64 {
+    if rate.is_nan() { 0.0 } else { rate.clamp(0.0, 1.0) }
+}
+
+/// A party member in a generated log
+#[derive(Debug, Clone)]
+pub struct PlayerConfig {
+    pub name: String,
+    pub aspect: Aspect,
+    /// Skill names used for damage, picked at random for each hit
+    pub skills: Vec<String>,
+    /// Chance of each hit being a critical hit, 0.0 - 1.0
+    pub crit_rate: f64,
+}
+
+impl PlayerConfig {
+    pub fn new(name: &str, aspect: Aspect) -> Self {
+        Self {
+            name: name.to_string(),
+            aspect,
+            skills: vec!["Strike".to_string(), "HeavyStrike".to_string(), "Finisher".to_string()],
+            crit_rate: 0.2,
+        }
+    }
+
+    pub fn skills(self, skills: Vec<String>) -> Self {
+        Self {
+            skills,
+            ..self
+        }
+    }
+
+    /// Chance of each hit being a critical hit, clamped to 0.0 - 1.0
+    pub fn crit_rate(self, crit_rate: f64) -> Self {
+        Self {
+            crit_rate: valid_rate(crit_rate),
+            ..self
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct GeneratorOptions {
+    seed: u64,
+    players: Vec<PlayerConfig>,
+    dives: usize,
+    combats: usize,
+    turns: usize,
+    hits: usize,
+    damage: (i64, i64),
+    status_effects: Vec<String>,
+    status_rate: f64,
+    orb_rate: f64,
+    dodge_rate: f64,
+    noise: usize,
+}
+
+impl Default for GeneratorOptions {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            players: DEFAULT_PARTY.into_iter().enumerate()
+                .map(|(index, aspect)| PlayerConfig::new(&format!("Player{}", index + 1), aspect))
+                .collect(),
+            dives: 1,
+            combats: 5,
+            turns: 8,
+            hits: 3,
+            damage: (5, 60),
+            status_effects: vec!["Burn".to_string(), "Poison".to_string(), "Bleed".to_string()],
+            status_rate: 0.3,
+            orb_rate: 0.5,
+            dodge_rate: 0.05,
+            noise: 4,
+        }
+    }
+}
+
+impl GeneratorOptions {
+    /// Seed for the random number generator, the same options and seed always produce the same log
+    pub fn seed(self, seed: u64) -> Self {
+        Self { seed, ..self }
+    }
+
+    /// Party members, the first is the pov character
+    pub fn players(self, players: Vec<PlayerConfig>) -> Self {
+        Self { players, ..self }
+    }
+
+    pub fn dives(self, dives: usize) -> Self {
+        Self { dives, ..self }
+    }
+
+    /// Combats per dive
+    pub fn combats(self, combats: usize) -> Self {
+        Self { combats, ..self }
+    }
+
+    /// Turns per combat
+    pub fn turns(self, turns: usize) -> Self {
+        Self { turns, ..self }
+    }
+
+    /// Hits per player per turn
+    pub fn hits(self, hits: usize) -> Self {
+        Self { hits, ..self }
+    }
+
+    /// Inclusive range of damage for a single hit
+    pub fn damage(self, min: i64, max: i64) -> Self {
+        Self { damage: (min, max.max(min)), ..self }
+    }
+
+    /// Status effects that may be applied on a hit
+    pub fn status_effects(self, status_effects: Vec<String>) -> Self {
+        Self { status_effects, ..self }
+    }
+
+    /// Chance of applying a status effect on each hit, clamped to 0.0 - 1.0
+    pub fn status_rate(self, status_rate: f64) -> Self {
+        Self { status_rate: valid_rate(status_rate), ..self }
+    }
+
+    /// Chance of each player picking up an orb each turn, clamped to 0.0 - 1.0
+    pub fn orb_rate(self, orb_rate: f64) -> Self {
+        Self { orb_rate: valid_rate(orb_rate), ..self }
+    }
+
+    /// Chance of each hit being dodged, clamped to 0.0 - 1.0
+    pub fn dodge_rate(self, dodge_rate: f64) -> Self {
+        Self { dodge_rate: valid_rate(dodge_rate), ..self }
+    }
+
+    /// Ignored lines added after each hit, real logs are mostly noise
+    pub fn noise(self, noise: usize) -> Self {
+        Self { noise, ..self }
+    }
+}
+
+/// Generates syntactically valid Inkbound logs, for tests, benchmarks and demos
+pub struct LogGenerator {
+    options: GeneratorOptions,
+    rng: StdRng,
+    /// Time of the next line, in hundredths of a second
+    clock: u64,
+    next_enemy: i64,
+}
+
+impl LogGenerator {
+    pub fn new(options: GeneratorOptions) -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(options.seed),
+            options,
+            clock: 0,
+            next_enemy: FIRST_ENEMY_HANDLE,
+        }
+    }
+
+    /// Format a line with a timestamp, advancing the clock slightly
+    fn line(&mut self, body: &str, fields: &[(&str, String)]) -> String {
+        self.clock += self.rng.gen_range(1..20);
+        let seconds = self.clock / 100;
+        let mut line = format!(
+            "{}T{:02}:{:02}:{:02} {:02} I {body}",
+            seconds / 86400, seconds / 3600 % 24, seconds / 60 % 60, seconds % 60, self.clock % 100,
+        );
+        for (name, value) in fields {
+            line = line.replace(&format!("{{{name}}}"), value);
+        }
+        line
+    }
+
+    fn chance(&mut self, rate: f64) -> bool {
+        // PlayerConfig's fields are public, so its crit rate may not have gone through a setter
+        self.rng.gen_bool(valid_rate(rate))
+    }
+
+    fn noise(&mut self, target: i64, out: &mut Vec<String>) {
+        for _ in 0..self.options.noise {
+            let noise = NOISE[self.rng.gen_range(0..NOISE.len())];
+            let line = self.line(noise, &[("target", target.to_string())]);
+            out.push(line);
+        }
+    }
+
+    /// Lines written when the pov character joins the hub
+    pub fn header(&mut self) -> Vec<String> {
+        let party: u128 = self.rng.gen();
+        let party = format!("{:08x}-{:04x}-{:04x}-{:04x}-{:012x}", party >> 96, party >> 80 & 0xffff, party >> 64 & 0xffff, party >> 48 & 0xffff, party & 0xffff_ffff_ffff);
+        let name = self.options.players.first().map(|p| p.name.clone()).unwrap_or_default();
+        vec![
+            self.line(JOIN_HUB, &[("character", format!("{:011}", self.options.seed % 100_000_000_000)), ("name", name), ("party", party)]),
+        ]
+    }
+
+    /// Lines for a single dive, from start to end
+    pub fn dive(&mut self) -> Vec<String> {
+        let players = self.options.players.clone();
+        let mut out = vec![self.line(START_DIVE, &[("solo", if players.len() <= 1 { "True" } else { "False" }.to_string())])];
+
+        for (handle, player) in (FIRST_PLAYER_HANDLE..).zip(players.iter()) {
+            let class = aspects::registry().get(&player.aspect).map(|info| info.id.clone());
+            let class = match (&player.aspect, class) {
+                (_, Some(class)) => class,
+                (Aspect::Unknown(id), None) => id.clone(),
+                (aspect, None) => format!("{aspect:?}"),
+            };
+            let line = self.line(UNIT_CLASS, &[("source", handle.to_string()), ("class", class)]);
+            out.push(line);
+        }
+
+        for _ in 0..self.options.combats {
+            self.combat(&players, &mut out);
+        }
+
+        out.push(self.line(END_DIVE, &[]));
+        out
+    }
+
+    fn combat(&mut self, players: &[PlayerConfig], out: &mut Vec<String>) {
+        let zone = self.next_enemy;
+        let enemies: Vec<i64> = (zone + 2..zone + 2 + self.rng.gen_range(2..5)).collect();
+        self.next_enemy = enemies.last().unwrap_or(&zone) + 1;
+        let mut stacks = vec![0; enemies.len()];
+
+        out.push(self.line(START_COMBAT, &[("zone", zone.to_string()), ("trigger", (zone + 1).to_string())]));
+
+        for _ in 0..self.options.turns {
+            for (handle, player) in (FIRST_PLAYER_HANDLE..).zip(players.iter()) {
+                out.push(self.line(NEXT_TURN, &[("source", handle.to_string())]));
+
+                for _ in 0..self.options.hits {
+                    let target_index = self.rng.gen_range(0..enemies.len());
+                    let target = enemies[target_index];
+                    let skill = player.skills.choose(&mut self.rng).cloned().unwrap_or("Strike".to_string());
+                    let crit = self.chance(player.crit_rate);
+                    let dodged = self.chance(self.options.dodge_rate);
+                    let amount = self.rng.gen_range(self.options.damage.0..=self.options.damage.1) * if crit { 2 } else { 1 };
+
+                    out.push(self.line(REGISTER_NAME, &[("name", player.name.clone()), ("source", handle.to_string()), ("skill", skill.clone())]));
+                    out.push(self.line(DAMAGE, &[
+                        ("target", target.to_string()),
+                        ("source", handle.to_string()),
+                        ("team", "Enemy".to_string()),
+                        ("amount", amount.to_string()),
+                        ("crit", if crit { "True" } else { "False" }.to_string()),
+                        ("dodged", if dodged { "True" } else { "False" }.to_string()),
+                        ("skill", skill),
+                    ]));
+
+                    if !self.options.status_effects.is_empty() && self.chance(self.options.status_rate) {
+                        let effect = self.options.status_effects.choose(&mut self.rng).cloned().unwrap_or_default();
+                        let added = self.rng.gen_range(1..6);
+                        stacks[target_index] += added;
+                        let total = stacks[target_index];
+                        out.push(self.line(ADD_STATUS, &[
+                            ("target", target.to_string()),
+                            ("source", handle.to_string()),
+                            ("effect", effect),
+                            ("added", added.to_string()),
+                            ("total", total.to_string()),
+                        ]));
+                    }
+                    self.noise(target, out);
+                }
+
+                // After the player's abilities, so their name is already known
+                if self.chance(self.options.orb_rate) {
+                    out.push(self.line(ORB_PICKUP, &[("source", handle.to_string())]));
+                }
+            }
+
+            // Enemies hit back
+            for &enemy in enemies.iter() {
+                let target = FIRST_PLAYER_HANDLE + self.rng.gen_range(0..players.len().max(1)) as i64;
+                let amount = self.rng.gen_range(self.options.damage.0..=self.options.damage.1) / 2;
+                out.push(self.line(DAMAGE, &[
+                    ("target", target.to_string()),
+                    ("source", enemy.to_string()),
+                    ("team", "Friendly".to_string()),
+                    ("amount", amount.to_string()),
+                    ("crit", "False".to_string()),
+                    ("dodged", "False".to_string()),
+                    ("skill", "Bite".to_string()),
+                ]));
+            }
+        }
+
+        out.push(self.line(END_COMBAT, &[]));
+    }
+
+    /// Generate a full log, the header followed by all dives
+    pub fn generate(&mut self) -> String {
+        let mut lines = self.header();
+        for _ in 0..self.options.dives {
+            lines.extend(self.dive());
+        }
+        lines.join("\n") + "\n"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{LogParser, DataLog};
+
+    fn parse(log: &str) -> DataLog {
+        let mut parser = LogParser::new();
+        let mut datalog = DataLog::new();
+        datalog.handle_events(parser.parse_lines(&log.lines().collect::<Vec<&str>>()));
+        datalog
+    }
+
+    #[test]
+    fn seeded_output_is_reproducible() {
+        let options = GeneratorOptions::default().seed(7);
+        assert_eq!(LogGenerator::new(options.clone()).generate(), LogGenerator::new(options.clone()).generate());
+        assert_ne!(LogGenerator::new(options.clone()).generate(), LogGenerator::new(options.seed(8)).generate());
+    }
+
+    #[test]
+    fn generated_log_parses() {
+        let options = GeneratorOptions::default()
+            .players(vec![
+                PlayerConfig::new("Alice", Aspect::Godkeeper).crit_rate(0.0),
+                PlayerConfig::new("Bob", Aspect::Chainbreaker).skills(vec!["Chain".to_string()]),
+            ])
+            .dives(2)
+            .combats(3)
+            .orb_rate(1.0)
+            .status_effects(vec!["Burn".to_string()])
+            .status_rate(1.0);
+        let datalog = parse(&LogGenerator::new(options).generate());
+
+        assert_eq!(datalog.pov.as_deref(), Some("Alice"));
+        assert_eq!(datalog.dives.len(), 2);
+        let dive = &datalog.dives[0];
+        assert_eq!(dive.combats.len(), 3);
+
+        let alice = dive.player_stats.get_by_name("Alice").unwrap();
+        assert_eq!(alice.player_data.class, Aspect::Godkeeper);
+        assert!(alice.crit_totals.is_empty());
+        assert!(alice.total_damage_received > 0);
+        assert_eq!(alice.orb_pickups, 3 * 8);
+        assert!(alice.status_applied.contains_key("Burn"));
+
+        let bob = dive.player_stats.get_by_name("Bob").unwrap();
+        assert_eq!(bob.skill_totals.keys().collect::<Vec<&String>>(), vec!["Chain"]);
+    }
+
+    #[test]
+    fn invalid_rates() {
+        let options = GeneratorOptions::default()
+            .players(vec![PlayerConfig::new("Alice", Aspect::Godkeeper).crit_rate(f64::NAN)])
+            .orb_rate(2.0)
+            .dodge_rate(-1.0)
+            .status_effects(vec!["Burn".to_string()])
+            .status_rate(f64::INFINITY);
+        let datalog = parse(&LogGenerator::new(options).generate());
+
+        let alice = datalog.dives[0].player_stats.get_by_name("Alice").unwrap();
+        assert!(alice.crit_totals.is_empty());
+        assert_eq!(alice.orb_pickups, 5 * 8);
+        assert!(alice.status_applied.contains_key("Burn"));
+    }
+}
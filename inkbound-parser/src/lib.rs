@@ -1,15 +1,49 @@
 pub mod parser;
 pub mod aspects;
+pub mod sanitize;
+pub mod generator;
 
-pub fn parse_log_to_json(path: &String) -> String {
-    let mut log_parser = crate::parser::LogParser::new();
+pub fn parse_log_to_json(path: &String, options: crate::parser::LogParserOptions) -> std::io::Result<String> {
+    let mut log_parser = crate::parser::LogParser::with_options(options);
     let mut data_log = crate::parser::DataLog::new();
 
-    let file = std::fs::read_to_string(path).unwrap();
+    let file = std::fs::read_to_string(path)?;
     let file: Vec<&str> = file.split('\n').collect();
 
     let events = log_parser.parse_lines(file.as_slice());
     data_log.handle_events(events);
 
-    serde_json::to_string(&data_log).unwrap()
+    Ok(serde_json::to_string(&data_log).unwrap())
+}
+
+/// Merge logs from several members of the same party, see `parser::merge_logs`.
+/// Read errors include the path of the log that couldn't be read.
+pub fn merge_logs_to_json(paths: &[String], options: crate::parser::LogParserOptions) -> std::io::Result<String> {
+    let files = paths.iter()
+        .map(|path| std::fs::read_to_string(path).map_err(|e| std::io::Error::new(e.kind(), format!("{path}: {e}"))))
+        .collect::<std::io::Result<Vec<String>>>()?;
+    let files: Vec<&str> = files.iter().map(String::as_str).collect();
+
+    let data_log = crate::parser::merge_logs(&files, options);
+
+    Ok(serde_json::to_string(&data_log).unwrap())
+}
+
+/// Anonymize a log so it can be shared, see `sanitize::Sanitizer`
+pub fn sanitize_log(path: &String, options: crate::sanitize::SanitizeOptions) -> std::io::Result<String> {
+    let file = std::fs::read_to_string(path)?;
+
+    Ok(crate::sanitize::Sanitizer::new(options).sanitize(&file))
+}
+
+/// Parse a single log with diagnostics enabled, and return the coverage report
+pub fn diagnose_log(path: &String, options: crate::parser::LogParserOptions) -> std::io::Result<crate::parser::ParserDiagnostics> {
+    let mut log_parser = crate::parser::LogParser::with_options(options.diagnostics(true));
+
+    let file = std::fs::read_to_string(path)?;
+    for line in file.lines().filter(|l| !l.trim().is_empty()) {
+        log_parser.parse_line(line);
+    }
+
+    Ok(log_parser.diagnostics().cloned().unwrap_or_default())
 }
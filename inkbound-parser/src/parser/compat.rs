@@ -0,0 +1,112 @@
+use std::{cmp::Ordering, sync::Arc};
+
+use serde::{Serialize, Deserialize};
+
+use super::LinePatterns;
+
+/// Game version as reported in the log header, e.g. "1.0.3"
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct GameVersion(pub String);
+
+impl GameVersion {
+    /// Numeric components of the version, anything unparseable is treated as zero
+    fn parts(&self) -> Vec<u64> {
+        self.0.split('.').map(|p| p.trim().parse().unwrap_or(0)).collect()
+    }
+
+    /// Compare only the first `len` components, missing components are treated as zero
+    fn cmp_prefix(&self, other: &GameVersion, len: usize) -> Ordering {
+        let (ours, theirs) = (self.parts(), other.parts());
+        (0..len)
+            .map(|i| ours.get(i).unwrap_or(&0).cmp(theirs.get(i).unwrap_or(&0)))
+            .find(|o| o.is_ne())
+            .unwrap_or(Ordering::Equal)
+    }
+}
+
+impl std::fmt::Display for GameVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Inclusive range of game versions a set of patterns is known to work with.
+/// The max is matched as a prefix, so a max of "1.0" also covers "1.0.5".
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VersionRange {
+    pub min: GameVersion,
+    pub max: GameVersion,
+}
+
+impl VersionRange {
+    pub fn contains(&self, version: &GameVersion) -> bool {
+        !self.is_below(version) && !self.is_above(version)
+    }
+
+    /// Version is older than anything in this range
+    pub fn is_below(&self, version: &GameVersion) -> bool {
+        let len = self.min.parts().len().max(version.parts().len());
+        version.cmp_prefix(&self.min, len).is_lt()
+    }
+
+    /// Version is newer than anything in this range
+    pub fn is_above(&self, version: &GameVersion) -> bool {
+        version.cmp_prefix(&self.max, self.max.parts().len()).is_gt()
+    }
+}
+
+/// Detected game version, and how well the parser is expected to handle it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GameVersionInfo {
+    pub version: GameVersion,
+    /// Versions supported by the selected compatibility profile, if it declares any
+    pub profile: Option<VersionRange>,
+    /// Version is newer than every known profile, so parsing may be inaccurate
+    pub newer_than_known: bool,
+}
+
+/// Pick the compatibility profile to use for a given game version.
+/// Returns the index of the profile, and whether the version is newer than every known profile.
+pub(crate) fn select_profile(profiles: &[Arc<LinePatterns>], version: &GameVersion) -> (usize, bool) {
+    // Profiles without a declared range are treated as matching anything, e.g. user overrides
+    if let Some(index) = profiles.iter().position(|p| p.versions().is_none_or(|r| r.contains(version))) {
+        return (index, false);
+    }
+
+    let newest = profiles.iter().enumerate()
+        .filter_map(|(i, p)| p.versions().map(|r| (i, r)))
+        .max_by(|(_, a), (_, b)| a.max.cmp_prefix(&b.max, a.max.parts().len().max(b.max.parts().len())));
+
+    match newest {
+        Some((index, range)) if range.is_above(version) => (index, true),
+        // Older than the newest, so use the newest profile that starts at or before it
+        _ => {
+            let index = profiles.iter()
+                .rposition(|p| p.versions().is_some_and(|r| !r.is_below(version)))
+                .unwrap_or(0);
+            (index, false)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(min: &str, max: &str) -> VersionRange {
+        VersionRange { min: GameVersion(min.to_string()), max: GameVersion(max.to_string()) }
+    }
+
+    #[test]
+    fn version_range() {
+        let r = range("1.0", "1.1");
+        assert!(r.contains(&GameVersion("1.0".to_string())));
+        assert!(r.contains(&GameVersion("1.0.7".to_string())));
+        assert!(r.contains(&GameVersion("1.1.12".to_string())));
+        assert!(r.is_above(&GameVersion("1.2".to_string())));
+        assert!(r.is_above(&GameVersion("2.0.1".to_string())));
+        assert!(r.is_below(&GameVersion("0.9.9".to_string())));
+        assert!(!r.contains(&GameVersion("0.9.9".to_string())));
+    }
+}
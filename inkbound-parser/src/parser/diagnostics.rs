@@ -0,0 +1,99 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+/// Maximum length of example lines kept for partial matches
+const EXAMPLE_LENGTH: usize = 512;
+
+/// A line that contained a pattern's keyword, but failed its full regex
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct PartialMatch {
+    pub count: usize,
+    /// First line that failed to match, truncated
+    pub example: String,
+}
+
+/// Coverage information collected by the LogParser when diagnostics are enabled
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct ParserDiagnostics {
+    /// Total lines seen
+    pub lines: usize,
+    /// Lines that matched a pattern
+    pub matched: usize,
+    /// Ignored lines, grouped by their event-type prefix
+    pub ignored: BTreeMap<String, usize>,
+    /// Lines that contained a known keyword but failed the full pattern, by pattern name
+    pub partial: BTreeMap<&'static str, PartialMatch>,
+}
+
+impl ParserDiagnostics {
+    pub(crate) fn record_ignored(&mut self, line: &str) {
+        *self.ignored.entry(line_prefix(line)).or_default() += 1;
+    }
+
+    pub(crate) fn record_partial(&mut self, pattern: &'static str, line: &str) {
+        let entry = self.partial.entry(pattern).or_default();
+        if entry.count == 0 {
+            entry.example = line.trim_end().chars().take(EXAMPLE_LENGTH).collect();
+        }
+        entry.count += 1;
+    }
+
+    /// Ignored line groups, most common first
+    pub fn ignored_by_count(&self) -> Vec<(&String, &usize)> {
+        let mut ignored: Vec<_> = self.ignored.iter().collect();
+        ignored.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+        ignored
+    }
+}
+
+impl std::fmt::Display for ParserDiagnostics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Lines: {}, matched: {}, ignored: {}", self.lines, self.matched, self.lines - self.matched)?;
+
+        writeln!(f, "\nPartial matches (keyword found, pattern failed):")?;
+        if self.partial.is_empty() {
+            writeln!(f, "  none")?;
+        }
+        for (pattern, partial) in self.partial.iter() {
+            writeln!(f, "  {:>8}  {pattern}\n            e.g. {}", partial.count, partial.example)?;
+        }
+
+        writeln!(f, "\nIgnored lines by prefix:")?;
+        for (prefix, count) in self.ignored_by_count() {
+            writeln!(f, "  {count:>8}  {prefix}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Reduce a line to a rough event-type prefix, so that similar lines group together.
+///  e.g. "0T23:17:51 70 I [EventSystem] broadcasting EventOnUnitMoved-WorldState..." -> "broadcasting EventOnUnitMoved"
+fn line_prefix(line: &str) -> String {
+    // Strip the "<time> <ms> <level>" header
+    let body = line.splitn(4, ' ').nth(3).unwrap_or(line).trim();
+
+    if let Some((_, event)) = body.split_once("broadcasting ") {
+        let event = event.split(['-', ' ']).next().unwrap_or_default();
+        return format!("broadcasting {event}");
+    }
+
+    // Otherwise use the first few words, masking anything that looks like an id, name or value
+    body.split_whitespace()
+        .take(3)
+        .map(|word| if word.chars().any(|c| c.is_ascii_digit() || c == '(' || c == ':') { "*" } else { word })
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prefixes() {
+        assert_eq!(line_prefix("0T23:17:51 70 I [EventSystem] broadcasting EventOnUnitMoved-WorldStateChangeMoveUnit-UnitHandle:(EntityHandle:5)"), "broadcasting EventOnUnitMoved");
+        assert_eq!(line_prefix("0T23:17:51 71 I [AnimationController] Playing animation Idle_Combat on (EntityHandle:5)"), "[AnimationController] Playing animation");
+        assert_eq!(line_prefix("0T23:17:51 71 I Loaded 12 assets"), "Loaded * assets");
+    }
+}
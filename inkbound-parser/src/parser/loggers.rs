@@ -1,7 +1,7 @@
 use log::*;
-use serde::Serialize;
+use serde::{Serialize, Deserialize};
 
-use super::{Event, playerstats::PlayerStatList};
+use super::{Event, GameVersionInfo, playerstats::PlayerStatList};
 
 // // TODO: Probably fold this into PlayerStats
 // fn apply_damage(devent: &Event, player_stats: &mut HashMap<String, PlayerStats>) {
@@ -25,7 +25,7 @@ use super::{Event, playerstats::PlayerStatList};
 // }
 
 
-#[derive(Debug, Serialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct CombatLog {
     pub player_stats: PlayerStatList,
     // player_stats: HashMap<String, PlayerStats>, // EntityHandle/id -> PlayerStats
@@ -41,7 +41,7 @@ impl CombatLog {
         }
     }
     
-    fn handle_event(&mut self, event: Event) {
+    fn handle_event(&mut self, event: &Event) {
         match event {
             Event::DamageDealt(_, dmg) => self.player_stats.apply_dealt_damage(dmg),
             Event::DamageReceived(_, dmg) => self.player_stats.apply_received_damage(dmg),
@@ -55,23 +55,40 @@ impl CombatLog {
     }
 }
 
-#[derive(Debug, Serialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct DiveLog {
+    /// Index of the Session this dive was played in, see DataLog.sessions
+    pub session: usize,
     pub player_stats: PlayerStatList,
     // player_stats: HashMap<String, PlayerStats>,
     pub combats: Vec<CombatLog>, // Reverse order list of combats, current is always first
+    /// Only the totals are left, see DataLog::compact
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub compacted: bool,
 }
 
 impl DiveLog {
     pub fn new() -> Self {
         Self {
+            session: 0,
             player_stats: PlayerStatList::new(),
             combats: Vec::new(),
+            compacted: false,
         }
     }
 
-    pub fn handle_event(&mut self, event: Event) {
-        match event.clone() {
+    /// Copy of just the dive's totals per player, without the combats or skill breakdowns
+    pub fn summary(&self) -> Self {
+        Self {
+            session: self.session,
+            player_stats: self.player_stats.summary(),
+            combats: Vec::new(),
+            compacted: true,
+        }
+    }
+
+    pub fn handle_event(&mut self, event: &Event) {
+        match event {
             Event::StartCombat(_) => self.combats.insert(0, CombatLog::new()),
             Event::DamageDealt(_, dmg) => self.player_stats.apply_dealt_damage(dmg),
             Event::DamageReceived(_, dmg) => self.player_stats.apply_received_damage(dmg),
@@ -95,10 +112,25 @@ impl Default for DiveLog {
     }
 }
 
-#[derive(Serialize, Debug, Clone, Default)]
+/// Dives played in one game launch with one character.
+/// A new session starts when the game is launched, or when joining the hub with a different character.
+/// Launches are noticed by the game recreating its log, see `new_session`, or from a version line if the patterns detect one.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Session {
+    /// Character played in this session, once known from joining the hub
+    pub character: Option<String>,
+    /// Line number the session started on
+    pub start_line: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct DataLog {
     pub dives: Vec<DiveLog>,
+    /// Sessions in the order they were played, oldest first
+    pub sessions: Vec<Session>,
+    /// Character of the current session
     pub pov: Option<String>,
+    pub game_version: Option<GameVersionInfo>,
 }
 
 impl DataLog {
@@ -106,17 +138,78 @@ impl DataLog {
         Self::default()
     }
 
+    pub fn current_session(&self) -> Option<&Session> {
+        self.sessions.last()
+    }
+
+    /// Session a dive was played in
+    pub fn session_of(&self, dive: &DiveLog) -> Option<&Session> {
+        self.sessions.get(dive.session)
+    }
+
+    /// Character whose log a dive was read from, i.e. the one played in its session, falling back to the current one
+    pub fn pov_of(&self, dive: &DiveLog) -> Option<&str> {
+        self.session_of(dive)
+            .and_then(|s| s.character.as_deref())
+            .or(self.pov.as_deref())
+    }
+
+    /// Characters played across all sessions, in order of first appearance
+    pub fn characters(&self) -> Vec<&str> {
+        let mut characters: Vec<&str> = Vec::new();
+        for character in self.sessions.iter().filter_map(|s| s.character.as_deref()) {
+            if !characters.contains(&character) {
+                characters.push(character);
+            }
+        }
+        characters
+    }
+
+    fn start_session(&mut self, start_line: usize, character: Option<String>) {
+        match self.sessions.len().checked_sub(1) {
+            // No dives in the current session yet, so just update it
+            Some(current) if self.dives.first().is_none_or(|d| d.session != current) => {
+                self.sessions[current].character = character;
+            },
+            _ => {
+                debug!("starting new session");
+                self.sessions.push(Session { character, start_line });
+            },
+        }
+    }
+
+    /// Start a new session, e.g. when the game has been restarted and the log file recreated
+    pub fn new_session(&mut self) {
+        self.start_session(0, None);
+    }
+
     pub fn handle_event(&mut self, event: Event) {
         match event {
             Event::StartDive(_) => {
                 debug!("starting new dive");
-                self.dives.insert(0, DiveLog::new())
+                if self.sessions.is_empty() {
+                    self.sessions.push(Session::default());
+                }
+                let mut dive = DiveLog::new();
+                dive.session = self.sessions.len() - 1;
+                self.dives.insert(0, dive)
+            },
+            Event::SetSelf(line, name, _) => {
+                let same_character = self.current_session().is_some_and(|s| s.character.as_ref() == Some(&name));
+                if !same_character {
+                    self.start_session(line.number, Some(name.clone()));
+                }
+                self.pov = Some(name)
+            },
+            Event::GameVersion(line, info) => {
+                // Version is logged at launch
+                self.start_session(line.number, None);
+                self.game_version = Some(info)
             },
-            Event::SetSelf(_, name) => self.pov = Some(name),
             _ => {
                 // debug!("propogating event: {:?}", event);
                 if let Some(dive) = self.dives.get_mut(0) {
-                    dive.handle_event(event);
+                    dive.handle_event(&event);
                 }
             }
             // _ => debug!("{:?}", event),
@@ -126,4 +219,88 @@ impl DataLog {
     pub fn handle_events(&mut self, events: Vec<Event>) {
         events.into_iter().for_each(|e| self.handle_event(e));
     }
+
+    /// Keep full detail for only the `keep` most recent dives, replacing older ones with their summary.
+    /// The most recent dive is always kept, as it may still be in progress.
+    /// Returns the full dives that were compacted, oldest first, e.g. to archive them
+    pub fn compact(&mut self, keep: usize) -> Vec<DiveLog> {
+        let mut compacted: Vec<DiveLog> = self.dives.iter_mut()
+            .skip(keep.max(1))
+            .filter(|dive| !dive.compacted)
+            .map(|dive| {
+                let summary = dive.summary();
+                std::mem::replace(dive, summary)
+            })
+            .collect();
+        compacted.reverse();
+        compacted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{LineInfo, GameVersion};
+
+    fn line(number: usize) -> LineInfo {
+        LineInfo { number, text: None }
+    }
+
+    fn version(number: usize) -> Event {
+        Event::GameVersion(line(number), GameVersionInfo { version: GameVersion("1.0".to_string()), profile: None, newer_than_known: false })
+    }
+
+    #[test]
+    fn sessions() {
+        let mut datalog = DataLog::new();
+        datalog.handle_events(vec![
+            version(1),
+            Event::SetSelf(line(2), "Main".to_string(), None),
+            Event::StartDive(line(3)),
+            // Back to the hub, same character
+            Event::SetSelf(line(4), "Main".to_string(), None),
+            Event::StartDive(line(5)),
+            // Switch to an alt
+            Event::SetSelf(line(6), "Alt".to_string(), None),
+            // Switch again without playing a dive
+            Event::SetSelf(line(7), "Alt2".to_string(), None),
+            Event::StartDive(line(8)),
+            // Relaunch
+            version(9),
+            Event::SetSelf(line(10), "Main".to_string(), None),
+            Event::StartDive(line(11)),
+        ]);
+
+        let characters: Vec<Option<&str>> = datalog.sessions.iter().map(|s| s.character.as_deref()).collect();
+        assert_eq!(characters, vec![Some("Main"), Some("Alt2"), Some("Main")]);
+        assert_eq!(datalog.sessions[1].start_line, 6);
+        assert_eq!(datalog.dives.iter().map(|d| d.session).collect::<Vec<usize>>(), vec![2, 1, 0, 0]);
+        assert_eq!(datalog.characters(), vec!["Main", "Alt2"]);
+        assert_eq!(datalog.pov.as_deref(), Some("Main"));
+    }
+
+    #[test]
+    fn compact() {
+        let log = crate::generator::LogGenerator::new(crate::generator::GeneratorOptions::default().seed(3).dives(4)).generate();
+        let mut datalog = DataLog::new();
+        datalog.handle_events(crate::parser::LogParser::new().parse_lines(&log.lines().collect::<Vec<&str>>()));
+        let full = datalog.clone();
+
+        let compacted = datalog.compact(2);
+        assert_eq!(compacted.len(), 2);
+        assert!(datalog.compact(2).is_empty());
+        for (dive, original) in datalog.dives.iter().zip(full.dives.iter()) {
+            let totals = |d: &DiveLog| d.player_stats.iter().map(|p| (p.total_damage_dealt, p.orb_pickups)).collect::<Vec<_>>();
+            assert_eq!(totals(dive), totals(original));
+            assert_eq!(dive.compacted, dive.combats.is_empty());
+        }
+        // Returned oldest first, with everything still in them
+        assert_eq!(compacted[0].combats.len(), full.dives[3].combats.len());
+        assert!(datalog.dives[3].player_stats.iter().all(|p| p.skill_totals.is_empty()));
+
+        // The current dive is never compacted
+        assert_eq!(datalog.compact(0).len(), 1);
+        assert!(!datalog.dives[0].compacted);
+        assert_eq!(datalog.dives[0].combats.len(), full.dives[0].combats.len());
+    }
 }
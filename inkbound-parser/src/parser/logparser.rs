@@ -1,29 +1,86 @@
-use std::collections::HashMap;
+use std::sync::Arc;
 
-use lazy_regex::*;
 use log::*;
-use serde::Serialize;
+use serde::{Serialize, Deserialize};
 
 use crate::aspects::Aspect;
+use super::{Event, DamageEventData, Entity, DamageDirection, AddStatusEffectData, LineInfo, GameVersion, GameVersionInfo};
+use super::compat::select_profile;
+use super::diagnostics::ParserDiagnostics;
+use super::party::Party;
+use super::patterns::{LinePatterns, LineKind};
+
+/// Number of lines at the start of a log to search for the game version
+const HEADER_LINES: usize = 50;
+
+#[derive(Debug, Default, Clone)]
+pub struct LogParserOptions {
+    keep_lines: bool,
+    diagnostics: bool,
+    profiles: Vec<Arc<LinePatterns>>,
+}
+
+impl LogParserOptions {
+    /// Retain the full text of each parsed line in the emitted Events.
+    /// Off by default, as damage lines alone can be several hundred bytes each.
+    pub fn keep_lines(self, keep_lines: bool) -> Self {
+        Self {
+            keep_lines,
+            ..self
+        }
+    }
 
-use super::{Event, DamageEventData, Entity, PlayerData, DamageDirection, AddStatusEffectData};
+    /// Collect ParserDiagnostics on ignored and partially matched lines
+    pub fn diagnostics(self, diagnostics: bool) -> Self {
+        Self {
+            diagnostics,
+            ..self
+        }
+    }
+
+    /// Add a set of line patterns as a compatibility profile, used instead of the bundled patterns.
+    /// May be called multiple times, the profile is then picked based on the game version in the log.
+    pub fn patterns(mut self, patterns: Arc<LinePatterns>) -> Self {
+        self.profiles.push(patterns);
+        self
+    }
+}
 
 #[derive(Debug, Serialize)]
 pub struct LogParser {
-    players: HashMap<i64, String>, // id -> name
-    classes: HashMap<i64, Aspect>, // id -> pre-translated Aspect
+    party: Party,
+    line_number: usize,
+    #[serde(skip)]
+    options: LogParserOptions,
+    #[serde(skip)]
+    profiles: Vec<Arc<LinePatterns>>,
+    #[serde(skip)]
+    patterns: Arc<LinePatterns>, // Currently selected profile
+    version: Option<GameVersion>,
+    diagnostics: Option<ParserDiagnostics>,
+    /// Lines that contained a pattern's keyword but failed the full pattern, counted even without diagnostics
+    #[serde(skip)]
+    partial_matches: usize,
 }
 
+/// What the LogParser has learned from a log so far, to continue parsing it later without reading it again
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ParserState {
+    party: Party,
+    line_number: usize,
+    version: Option<GameVersion>,
+}
 
 #[derive(Debug)]
 enum InternalEvent {
-    Damage(String, DamageEventData),
-    AddStatusEffect(String, AddStatusEffectData),
-    OrbPickup(String, Entity),
-    RegisterName(String, i64, String),
-    UnitClass(String, i64, String),
-    EndDive(String),
-    Unknown(String),
+    Damage(LineInfo, DamageEventData),
+    AddStatusEffect(LineInfo, AddStatusEffectData),
+    OrbPickup(LineInfo, Entity),
+    /// id, name, character id
+    RegisterName(i64, String, Option<String>),
+    UnitClass(i64, String),
+    EndDive(LineInfo),
+    Unknown,
 }
 
 #[derive(Debug)]
@@ -32,132 +89,217 @@ enum ParseEvent {
     Parsed(Event),
 }
 
-trait IdToPlayer {
-    fn to_player(self, players: &HashMap<i64, String>, classes: &HashMap<i64, Aspect>) -> Self;
-}
+impl LogParser {
+    pub fn new() -> Self {
+        Self::with_options(LogParserOptions::default())
+    }
 
-impl IdToPlayer for Entity {
-    fn to_player(self, players: &HashMap<i64, String>, classes: &HashMap<i64, Aspect>) -> Self {
-        match self {
-            Entity::Player(_) => self,
-            Entity::Id(id) => {
-                // TODO: clean this up, it's gross
-                let name = if let Some(name) = players.get(&id) {
-                    name.clone()
-                } else {
-                    // Return early, this isn't a player
-                    return Entity::Id(id);
-                };
-                let class = if let Some(class) = classes.get(&id) {
-                    class.clone()
-                } else {
-                    Aspect::Unknown(id.to_string())
-                };
-                Entity::Player(
-                    PlayerData {
-                        name,
-                        // TODO: probably Option this if it gets detected later
-                        class,
-                        id,
-                    }
-                )
-            }
+    pub fn with_options(options: LogParserOptions) -> Self {
+        let profiles = if options.profiles.is_empty() {
+            vec![LinePatterns::bundled()]
+        } else {
+            options.profiles.clone()
+        };
+
+        Self {
+            party: Party::default(),
+            line_number: 0,
+            // Use the first profile until the game version is known
+            patterns: profiles[0].clone(),
+            profiles,
+            version: None,
+            diagnostics: options.diagnostics.then(ParserDiagnostics::default),
+            partial_matches: 0,
+            options,
         }
     }
-}
 
-impl LogParser {
-    pub fn new() -> Self {
+    /// Continue parsing a log from where a previous parser left off, see `state`.
+    /// Diagnostics start over, as only lines parsed from here on are counted
+    pub fn with_state(options: LogParserOptions, state: ParserState) -> Self {
+        let mut parser = Self::with_options(options);
+        if let Some(version) = &state.version {
+            let (index, _) = select_profile(&parser.profiles, version);
+            parser.patterns = parser.profiles[index].clone();
+        }
         Self {
-            players: HashMap::new(),
-            classes: HashMap::new(),
+            party: state.party,
+            line_number: state.line_number,
+            version: state.version,
+            ..parser
         }
     }
 
-    fn do_parse(&mut self, line: &str) -> ParseEvent {
-        if let Some(caps) = regex!(r"EventOnUnitDamaged.*?TargetUnitHandle:\(EntityHandle:(?<target>\d+)\).*?SourceEntityHandle:\(EntityHandle:(?<source>\d+)\).*?DamageAmount:(?<damage>\d+).*?IsCriticalHit:(?<crit>True|False)-WasDodged:(?<dodged>True|False)-ActionData:ActionData-(?<ability>\w+)_Action").captures(line) {
-            ParseEvent::Internal(InternalEvent::Damage(line.to_string(), DamageEventData {
-                source: Entity::Id(caps.name("source").unwrap().as_str().parse().unwrap()),
-                target: Entity::Id(caps.name("target").unwrap().as_str().parse().unwrap()),
-                ability: caps.name("ability").unwrap().as_str().to_string(),
-                amount: caps.name("damage").unwrap().as_str().parse().unwrap(),
-                crit:   caps.name("crit").unwrap().as_str().to_lowercase().parse().unwrap(),
-                dodged: caps.name("dodged").unwrap().as_str().to_lowercase().parse().unwrap(),
-            }))
+    pub fn state(&self) -> ParserState {
+        ParserState {
+            party: self.party.clone(),
+            line_number: self.line_number,
+            version: self.version.clone(),
         }
-        // NOTE: this matches a lot of extra entity ids, may or may not be issue
-        else if let Some(caps) = regex_captures!(r"Setting unit class.*?UnitEntityHandle:\(EntityHandle:(\d+)\)-classType:(\w+)", line) {
-            ParseEvent::Internal(InternalEvent::UnitClass(line.to_string(), caps.1.parse().unwrap(), caps.2.to_string()))
+    }
+
+    /// Build the LineInfo for the line currently being parsed
+    fn line_info(&self, line: &str) -> LineInfo {
+        LineInfo {
+            number: self.line_number,
+            text: self.options.keep_lines.then(|| line.trim_end_matches(['\r', '\n']).to_string()),
         }
-        else if let Some(caps) = regex_captures!(r" I (\w+) \(EntityHandle:(\d+)\) is playing ability", line) {
-            ParseEvent::Internal(InternalEvent::RegisterName(line.to_string(), caps.2.parse().unwrap(), caps.1.to_string()))
+    }
+
+    /// Check the full pattern for lines that don't extract any data, noting any partial matches
+    fn is_match(&mut self, kind: LineKind, line: &str) -> bool {
+        let matched = self.patterns.is_match(kind, line);
+        if !matched {
+            self.record_partial(kind, line);
         }
-        else if let Some(caps) = regex_captures!(r"PlayerUnitHandle:\(EntityHandle:(\d+)\).*PickupData\-ManaOrbPickup", line) {
-            ParseEvent::Internal(InternalEvent::OrbPickup(line.to_string(), Entity::Id(caps.1.parse().unwrap())))
+        matched
+    }
+
+    fn record_partial(&mut self, kind: LineKind, line: &str) {
+        self.partial_matches += 1;
+        if let Some(diagnostics) = self.diagnostics.as_mut() {
+            diagnostics.record_partial(kind.name(), line);
         }
-        else if let Some(caps) = regex_captures!(r"Joining hub.*characterName: (.*), partyId", line) {
-            ParseEvent::Parsed(Event::SetSelf(line.to_string(), caps.1.to_string()))
+    }
+
+    fn do_parse(&mut self, line: &str) -> ParseEvent {
+        // Single pass over the line to figure out which (if any) of the capture regexes are worth running.
+        //  The vast majority of lines match nothing, so they bail out here.
+        let kinds = self.patterns.keywords(line);
+        if !kinds.matched_any() {
+            return ParseEvent::Internal(InternalEvent::Unknown)
         }
-        else if let Some(caps) = regex_captures!(r"EventOnUnitStatusEffectStacksAdded.*TargetUnitEntityHandle:\(EntityHandle:(?<target>\d+)\)-CasterUnitEntityHandle:\(EntityHandle:(?<source>\d+)\)-TargetUnitTeam:(?<targetteam>\w+).*StatusEffectData:StatusEffectData-(?<effectname>\w+)_StatusEffect.*StacksAdded:(?<added>\d+)-NewStacksValue:(?<newvalue>\d+)", line) {
-            let (_, target, source, targetteam, effectname, added, newvalue) = caps;
-            ParseEvent::Internal(InternalEvent::AddStatusEffect(line.to_string(), AddStatusEffectData {
-                source: Entity::Id(source.parse().unwrap()),
-                target: Entity::Id(target.parse().unwrap()),
-                target_team: match targetteam {
-                    "Friendly" => super::TargetUnitTeam::Friendly,
-                    "Enemy" => super::TargetUnitTeam::Enemy,
-                    _ => super::TargetUnitTeam::Unknown(targetteam.to_string()),
-                },
-                effectname: effectname.to_string(),
-                added: added.parse().unwrap(),
-                newvalue: newvalue.parse().unwrap(),
-            }))
+        let has = |kind: LineKind| kinds.matched(kind as usize);
+
+        // Captured numbers can still fail to parse (e.g. overflow), so those lines are treated as partial matches
+        if has(LineKind::Damage) {
+            let damage = self.patterns.captures(LineKind::Damage, line).and_then(|caps| Some(DamageEventData {
+                source: Entity::Id(caps["source"].parse().ok()?),
+                target: Entity::Id(caps["target"].parse().ok()?),
+                ability: caps["ability"].to_string(),
+                amount: caps["damage"].parse().ok()?,
+                crit:   caps["crit"].to_lowercase().parse().ok()?,
+                dodged: caps["dodged"].to_lowercase().parse().ok()?,
+            }));
+            if let Some(damage) = damage {
+                return ParseEvent::Internal(InternalEvent::Damage(self.line_info(line), damage))
+            }
+            self.record_partial(LineKind::Damage, line);
         }
-        else if regex_is_match!(r"Party run start triggered", line) {
-            ParseEvent::Parsed(Event::StartDive(line.to_string()))
+        // NOTE: this matches a lot of extra entity ids, may or may not be issue
+        if has(LineKind::UnitClass) {
+            let class = self.patterns.captures(LineKind::UnitClass, line)
+                .and_then(|caps| Some(InternalEvent::UnitClass(caps["id"].parse().ok()?, caps["class"].to_string())));
+            if let Some(class) = class {
+                return ParseEvent::Internal(class)
+            }
+            self.record_partial(LineKind::UnitClass, line);
         }
-        // TODO: Event::EndDive
-        else if regex_is_match!(r"EventOnCombatStarted", line) {
-            ParseEvent::Parsed(Event::StartCombat(line.to_string()))
+        if has(LineKind::RegisterName) {
+            let name = self.patterns.captures(LineKind::RegisterName, line).and_then(|caps| Some(InternalEvent::RegisterName(
+                caps["id"].parse().ok()?,
+                caps["name"].to_string(),
+                caps.name("character").map(|c| c.as_str().to_string()),
+            )));
+            if let Some(name) = name {
+                return ParseEvent::Internal(name)
+            }
+            self.record_partial(LineKind::RegisterName, line);
         }
-        else if regex_is_match!(r"EventOnCombatEndSequenceStarted", line) {
-            ParseEvent::Parsed(Event::EndCombat(line.to_string()))
+        if has(LineKind::OrbPickup) {
+            let id = self.patterns.captures(LineKind::OrbPickup, line).and_then(|caps| caps["id"].parse().ok());
+            if let Some(id) = id {
+                return ParseEvent::Internal(InternalEvent::OrbPickup(self.line_info(line), Entity::Id(id)))
+            }
+            self.record_partial(LineKind::OrbPickup, line);
         }
-        // TODO: This seems to appear once per player per turn, so this might need to be post-processed in the state machine
-        else if regex_is_match!(r"QuestObjective_TurnCount", line) {
-            ParseEvent::Parsed(Event::NextTurn(line.to_string()))
+        if has(LineKind::SetSelf) {
+            if let Some(caps) = self.patterns.captures(LineKind::SetSelf, line) {
+                return ParseEvent::Parsed(Event::SetSelf(
+                    self.line_info(line),
+                    caps["name"].to_string(),
+                    caps.name("party").map(|p| p.as_str().to_string()),
+                ))
+            }
+            self.record_partial(LineKind::SetSelf, line);
         }
-        else if regex_is_match!(r"broadcasting EventSetGameState-EndRun", line) {
-            ParseEvent::Internal(InternalEvent::EndDive(line.to_string()))
+        if has(LineKind::AddStatusEffect) {
+            let status = self.patterns.captures(LineKind::AddStatusEffect, line).and_then(|caps| {
+                let targetteam = &caps["targetteam"];
+                Some(AddStatusEffectData {
+                    source: Entity::Id(caps["source"].parse().ok()?),
+                    target: Entity::Id(caps["target"].parse().ok()?),
+                    target_team: match targetteam {
+                        "Friendly" => super::TargetUnitTeam::Friendly,
+                        "Enemy" => super::TargetUnitTeam::Enemy,
+                        _ => super::TargetUnitTeam::Unknown(targetteam.to_string()),
+                    },
+                    effectname: caps["effectname"].to_string(),
+                    added: caps["added"].parse().ok()?,
+                    newvalue: caps["newvalue"].parse().ok()?,
+                })
+            });
+            if let Some(status) = status {
+                return ParseEvent::Internal(InternalEvent::AddStatusEffect(self.line_info(line), status))
+            }
+            self.record_partial(LineKind::AddStatusEffect, line);
         }
-        else {
-            ParseEvent::Internal(InternalEvent::Unknown(line.to_string()))
+
+        // The remaining events don't extract any data, so only need to check for a match
+        let kind = [LineKind::StartDive, LineKind::StartCombat, LineKind::EndCombat, LineKind::NextTurn, LineKind::EndDive]
+            .into_iter()
+            .find(|&kind| has(kind) && self.is_match(kind, line));
+        match kind {
+            Some(LineKind::StartDive) => ParseEvent::Parsed(Event::StartDive(self.line_info(line))),
+            // TODO: Event::EndDive
+            Some(LineKind::StartCombat) => ParseEvent::Parsed(Event::StartCombat(self.line_info(line))),
+            Some(LineKind::EndCombat) => ParseEvent::Parsed(Event::EndCombat(self.line_info(line))),
+            // TODO: This seems to appear once per player per turn, so this might need to be post-processed in the state machine
+            Some(LineKind::NextTurn) => ParseEvent::Parsed(Event::NextTurn(self.line_info(line))),
+            Some(LineKind::EndDive) => ParseEvent::Internal(InternalEvent::EndDive(self.line_info(line))),
+            _ => ParseEvent::Internal(InternalEvent::Unknown),
         }
         // TODO: Clear player/class mappings after a dive
     }
 
     pub fn parse_line(&mut self, line: &str) -> Option<Event> {
-        match self.do_parse(line) {
+        self.line_number += 1;
+        if self.version.is_none() && self.line_number <= HEADER_LINES {
+            if let Some(version) = self.profiles.iter().find_map(|p| p.detect_version(line)) {
+                return Some(self.set_version(line, version));
+            }
+        }
+
+        let parsed = self.do_parse(line);
+        if let Some(diagnostics) = self.diagnostics.as_mut() {
+            diagnostics.lines += 1;
+            match parsed {
+                ParseEvent::Internal(InternalEvent::Unknown) => diagnostics.record_ignored(line),
+                _ => diagnostics.matched += 1,
+            }
+        }
+
+        match parsed {
             ParseEvent::Parsed(event) => Some(event),
-            ParseEvent::Internal(InternalEvent::Unknown(line)) => {
+            ParseEvent::Internal(InternalEvent::Unknown) => {
                 trace!("ignoring line: {}", line);
                 None
             },
-            ParseEvent::Internal(InternalEvent::RegisterName(_, id, name)) => {
+            ParseEvent::Internal(InternalEvent::RegisterName(id, name, character_id)) => {
                 // debug!("mapping id {} to player {}", id, name);
-                self.players.insert(id, name);
+                self.party.register_name(id, name, character_id);
                 None
             },
             ParseEvent::Internal(InternalEvent::Damage(line, dmg)) => {
                 Some(self.convert_damage(line, dmg))
             }
             ParseEvent::Internal(InternalEvent::AddStatusEffect(line, mut data)) => {
-                data.source = data.source.to_player(&self.players, &self.classes);
-                data.target = data.target.to_player(&self.players, &self.classes);
+                data.source = self.party.resolve(data.source);
+                data.target = self.party.resolve(data.target);
                 Some(Event::AddStatusEffect(line, data))
             }
             ParseEvent::Internal(InternalEvent::OrbPickup(s, id)) => {
-                match id.to_player(&self.players, &self.classes) {
+                match self.party.resolve(id) {
                     Entity::Id(id) => {
                         log::error!("unknown entity {id:?} apparently picked up an orb, ignoring");
                         None
@@ -166,29 +308,56 @@ impl LogParser {
                 }
             }
             // Register the EntityId -> Class mapping first, return the information RegisterPlayer when name is received
-            ParseEvent::Internal(InternalEvent::UnitClass(_, id, class_id)) => {
-                self.classes.insert(id, Aspect::from_id(&class_id));
+            ParseEvent::Internal(InternalEvent::UnitClass(id, class_id)) => {
+                self.party.set_class(id, Aspect::from_id(&class_id));
                 None
             },
             ParseEvent::Internal(InternalEvent::EndDive(line)) => {
-                self.players.clear();
-                self.classes.clear();
+                self.party.clear();
                 Some(Event::EndDive(line))
             }
         }
     }
 
+    /// Number of lines that looked like a known line, but failed to parse. See `ParserDiagnostics::partial` for details
+    pub fn partial_matches(&self) -> usize {
+        self.partial_matches
+    }
+
+    /// Coverage diagnostics collected so far, if enabled in LogParserOptions
+    pub fn diagnostics(&self) -> Option<&ParserDiagnostics> {
+        self.diagnostics.as_ref()
+    }
+
+    /// Switch to the compatibility profile matching the detected game version
+    fn set_version(&mut self, line: &str, version: GameVersion) -> Event {
+        let (index, newer_than_known) = select_profile(&self.profiles, &version);
+        self.patterns = self.profiles[index].clone();
+
+        if newer_than_known {
+            warn!("game version {version} is newer than any known log format, parsing may be inaccurate");
+        } else {
+            debug!("detected game version {version}");
+        }
+        self.version = Some(version.clone());
+
+        Event::GameVersion(self.line_info(line), GameVersionInfo {
+            version,
+            profile: self.patterns.versions().cloned(),
+            newer_than_known,
+        })
+    }
+
     /// Parse multiple lines, convert to a list of Events
     /// May return an empty vector if no lines are useful
     pub fn parse_lines(&mut self, lines: &[&str]) -> Vec<Event> {
         lines.iter().filter_map(|l| self.parse_line(l)).collect()
     }
 
-    fn convert_damage(&self, line: String, mut dmg: DamageEventData) -> Event {
-        // debug!("self.players = {:?}", self.players);
-        // debug!("self.classes = {:?}", self.classes);
-        dmg.source = dmg.source.to_player(&self.players, &self.classes);
-        dmg.target = dmg.target.to_player(&self.players, &self.classes);
+    fn convert_damage(&mut self, line: LineInfo, mut dmg: DamageEventData) -> Event {
+        // debug!("self.party = {:?}", self.party);
+        dmg.source = self.party.resolve(dmg.source);
+        dmg.target = self.party.resolve(dmg.target);
 
         // debug!("converting damage: {:?}", dmg);
         match dmg.into() {
@@ -285,7 +454,7 @@ mod tests {
         let line = parser.do_parse(L_UNIT_CLASS);
 
         match &line {
-            ParseEvent::Internal(InternalEvent::UnitClass(_, id, class)) => {
+            ParseEvent::Internal(InternalEvent::UnitClass(id, class)) => {
                 assert_eq!(*id, 22);
                 assert_eq!(*class, "C02".to_string());
             },
@@ -378,7 +547,10 @@ mod tests {
         let line = parser.do_parse(L_SET_SELF);
 
         match line {
-            ParseEvent::Parsed(Event::SetSelf(_, name)) => assert_eq!(name, "TestName".to_string()),
+            ParseEvent::Parsed(Event::SetSelf(_, name, party)) => {
+                assert_eq!(name, "TestName".to_string());
+                assert_eq!(party, Some("392f1b98-4d51-4379-8624-72cce1bab72b".to_string()));
+            },
             _ => {
                 println!("received {:?}", line);
                 panic!();
@@ -408,28 +580,183 @@ mod tests {
     }
 
     #[test]
-    #[ignore] // TODO: skip until there's a sanitized log to actually parse
-    fn test_logfile() {
-        // TODO: use parse_log_to_json
-        // env_logger::Builder::new()
-        //     .filter_level(log::LevelFilter::Debug)
-        //     .init();
+    fn parse_unknown_line() {
+        let mut parser = LogParser::new();
+
+        // No keyword at all
+        match parser.do_parse("0T23:17:51 71 I [AnimationController] Playing animation Idle_Combat") {
+            ParseEvent::Internal(InternalEvent::Unknown) => (),
+            line => panic!("received {:?}", line),
+        }
+        // Keyword present, but the full pattern doesn't match
+        match parser.do_parse("0T23:17:51 70 I [EventSystem] broadcasting EventOnUnitDamaged-WorldStateChangeDamageUnit") {
+            ParseEvent::Internal(InternalEvent::Unknown) => (),
+            line => panic!("received {:?}", line),
+        }
+    }
+
+    #[test]
+    fn class_after_first_damage() {
+        // The aspect is only logged after the player has already dealt damage
+        let mut parser = LogParser::new();
+        let mut datalog = DataLog::new();
+        datalog.handle_events(parser.parse_lines(&[L_START_DIVE, L_REGISTER_NAME, L_DAMAGE_NORMAL, L_UNIT_CLASS, L_DAMAGE_CRIT]));
+
+        let stats = &datalog.dives[0].player_stats;
+        assert_eq!(stats.len(), 1);
+        let player = stats.get_by_name("TestPlayer").unwrap();
+        assert_eq!(player.total_damage_dealt, 50);
+    }
+
+    #[test]
+    fn parse_line_info() {
+        let mut parser = LogParser::new();
+        parser.parse_line(L_REGISTER_NAME);
+
+        match parser.parse_line(L_START_COMBAT) {
+            Some(Event::StartCombat(info)) => assert_eq!(info, LineInfo { number: 2, text: None }),
+            line => panic!("received {:?}", line),
+        }
+
+        let mut parser = LogParser::with_options(LogParserOptions::default().keep_lines(true));
+        match parser.parse_line(&format!("{L_START_COMBAT}\n")) {
+            Some(Event::StartCombat(info)) => assert_eq!(info, LineInfo { number: 1, text: Some(L_START_COMBAT.to_string()) }),
+            line => panic!("received {:?}", line),
+        }
+    }
+
+    /// Bundled patterns with version detection on, for a made up header line, as the game's isn't known
+    fn versioned_options() -> LogParserOptions {
+        let mut file: PatternFile = ron::from_str(include_str!("patterns.ron")).unwrap();
+        file.version_pattern = Some(r"Game version: (?<version>\d+(?:\.\d+)+)".to_string());
+        file.game_versions = Some(VersionRange { min: GameVersion("1.0".to_string()), max: GameVersion("1.0".to_string()) });
+        LogParserOptions::default().patterns(std::sync::Arc::new(LinePatterns::compile(file).unwrap()))
+    }
+
+    #[test]
+    fn parse_game_version() {
+        // No version line is known, so the bundled patterns don't look for one
+        assert_eq!(LogParser::new().parse_line("0T23:10:02 11 I Game version: 1.0.3"), None);
+
+        let mut parser = LogParser::with_options(versioned_options());
+        match parser.parse_line("0T23:10:02 11 I Game version: 1.0.3") {
+            Some(Event::GameVersion(_, info)) => {
+                assert_eq!(info.version, GameVersion("1.0.3".to_string()));
+                assert!(!info.newer_than_known);
+            },
+            line => panic!("received {:?}", line),
+        }
+
+        let mut parser = LogParser::with_options(versioned_options());
+        match parser.parse_line("0T23:10:02 11 I Game version: 99.1") {
+            Some(Event::GameVersion(_, info)) => assert!(info.newer_than_known),
+            line => panic!("received {:?}", line),
+        }
+
+        // Only the log header is searched
+        let mut parser = LogParser::with_options(versioned_options());
+        for _ in 0..super::HEADER_LINES {
+            parser.parse_line(L_START_COMBAT);
+        }
+        assert_eq!(parser.parse_line("0T23:10:02 11 I Game version: 1.0.3"), None);
+    }
+
+    #[test]
+    fn parse_diagnostics() {
+        let mut parser = LogParser::new();
+        parser.parse_line(L_START_COMBAT);
+        assert!(parser.diagnostics().is_none());
+
+        let mut parser = LogParser::with_options(LogParserOptions::default().diagnostics(true));
+        parser.parse_lines(&[
+            L_START_COMBAT,
+            L_DAMAGE_NORMAL,
+            "0T23:17:51 70 I [EventSystem] broadcasting EventOnUnitMoved-WorldStateChangeMoveUnit-UnitHandle:(EntityHandle:78)",
+            "0T23:17:52 70 I [EventSystem] broadcasting EventOnUnitMoved-WorldStateChangeMoveUnit-UnitHandle:(EntityHandle:79)",
+            "0T23:17:51 70 I [EventSystem] broadcasting EventOnUnitDamaged-WorldStateChangeDamageUnit",
+        ]);
+
+        let diagnostics = parser.diagnostics().unwrap();
+        assert_eq!(diagnostics.lines, 5);
+        assert_eq!(diagnostics.matched, 2);
+        assert_eq!(diagnostics.ignored.get("broadcasting EventOnUnitMoved"), Some(&2));
+        assert_eq!(diagnostics.ignored.get("broadcasting EventOnUnitDamaged"), Some(&1));
+        assert_eq!(diagnostics.partial.get("damage").map(|p| p.count), Some(1));
+    }
+
+    #[test]
+    fn parse_overflowing_numbers() {
+        let mut parser = LogParser::with_options(LogParserOptions::default().diagnostics(true));
+        let huge = "99999999999999999999999";
+        let lines = [
+            L_DAMAGE_NORMAL.replace("DamageAmount:25", &format!("DamageAmount:{huge}")),
+            L_UNIT_CLASS.replace("EntityHandle:22", &format!("EntityHandle:{huge}")),
+            L_REGISTER_NAME.replace("EntityHandle:22", &format!("EntityHandle:{huge}")),
+            L_ORB_PICKUP.replace("EntityHandle:9", &format!("EntityHandle:{huge}")),
+            L_ADD_STATUS.replace("StacksAdded:5", &format!("StacksAdded:{huge}")),
+        ];
+        for line in lines.iter() {
+            assert_eq!(parser.parse_line(line), None);
+        }
+        assert_eq!(parser.diagnostics().unwrap().partial.len(), lines.len());
+        assert_eq!(parser.partial_matches(), lines.len());
+    }
+
+    #[test]
+    fn resume_from_state() {
+        let log = crate::generator::LogGenerator::new(crate::generator::GeneratorOptions::default().seed(7)).generate();
+        let lines: Vec<&str> = log.lines().collect();
+        let expected = LogParser::new().parse_lines(&lines);
+
+        let (first, rest) = lines.split_at(lines.len() / 2);
+        let mut parser = LogParser::new();
+        let mut events = parser.parse_lines(first);
+        let state: ParserState = serde_json::from_str(&serde_json::to_string(&parser.state()).unwrap()).unwrap();
+        let mut parser = LogParser::with_state(LogParserOptions::default(), state);
+        events.extend(parser.parse_lines(rest));
+        assert_eq!(events, expected);
+    }
 
-        // debug!("parsing log...");
+    #[test]
+    fn test_logfile() {
+        use crate::generator::{GeneratorOptions, LogGenerator, PlayerConfig};
+        use crate::parser::Aspect;
 
-        let mut log_parser = crate::parser::LogParser::new();
-        let mut data_log = crate::parser::DataLog::new();
+        let parse = |log: &str| {
+            let mut data_log = crate::parser::DataLog::new();
+            data_log.handle_events(LogParser::new().parse_lines(&log.lines().collect::<Vec<&str>>()));
+            data_log
+        };
 
-        let file = std::fs::read_to_string("logfile.log").unwrap();
-        let file: Vec<&str> = file.split('\n').collect();
+        // Sanitized logs shipped with the tests
+        let fixtures = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+        for path in std::fs::read_dir(fixtures).unwrap().map(|e| e.unwrap().path()) {
+            if path.extension().is_some_and(|e| e == "log") {
+                let data_log = parse(&std::fs::read_to_string(&path).unwrap());
+                assert!(!data_log.dives.is_empty(), "no dives in {}", path.display());
+                assert!(data_log.dives.iter().all(|d| d.player_stats.iter().next().is_some()), "no players in {}", path.display());
+                serde_json::to_string(&data_log).unwrap();
+            }
+        }
 
-        let events = log_parser.parse_lines(file.as_slice());
-        data_log.handle_events(events);
+        // Real logs can't be shared as-is, so parse a log prepared the same way a shared one would be
+        let players = vec![PlayerConfig::new("Alice", Aspect::Godkeeper), PlayerConfig::new("Bob", Aspect::Chainbreaker)];
+        let log = LogGenerator::new(GeneratorOptions::default().seed(3).players(players)).generate();
+        assert!(log.contains("Alice") && log.contains("Bob"));
+        let sanitized = crate::sanitize::Sanitizer::new(crate::sanitize::SanitizeOptions::default().drop_unrecognized(true)).sanitize(&log);
+        assert!(!sanitized.contains("Alice") && !sanitized.contains("Bob"));
 
-        println!("{}", serde_json::to_string(&data_log).unwrap());
+        // Only the names should change
+        let damage = |data_log: &crate::parser::DataLog| -> Vec<Vec<i64>> {
+            data_log.dives.iter().map(|dive| dive.player_stats.iter().map(|p| p.total_damage_dealt).collect()).collect()
+        };
+        let (original, sanitized) = (parse(&log), parse(&sanitized));
+        assert!(!original.dives.is_empty());
+        assert_eq!(damage(&sanitized), damage(&original));
     }
 
     #[test]
+    #[allow(clippy::get_first)] // Unchanged from before clippy was run on tests
     fn test_logfile_append() {
         use std::io::*;
         use std::fs::*;
@@ -488,7 +815,7 @@ mod tests {
         assert!(dive.is_some());
         let dive = dive.unwrap();
         // TODO: consider asserting combat info too
-        let testplayer = dive.player_stats.player_stats.get("TestPlayer");
+        let testplayer = dive.player_stats.get_by_name("TestPlayer");
         assert!(testplayer.is_some());
         let testplayer = testplayer.unwrap();
         assert!(testplayer.total_damage_dealt == 25);
@@ -508,7 +835,7 @@ mod tests {
         assert!(dive.is_some());
         let dive = dive.unwrap();
         // TODO: consider asserting combat info too
-        let testplayer = dive.player_stats.player_stats.get("TestPlayer");
+        let testplayer = dive.player_stats.get_by_name("TestPlayer");
         assert!(testplayer.is_some());
         let testplayer = testplayer.unwrap();
         assert!(testplayer.total_damage_dealt == 50);
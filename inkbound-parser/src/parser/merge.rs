@@ -0,0 +1,272 @@
+use std::collections::HashMap;
+
+use log::*;
+
+use super::{Event, Entity, PlayerData, PlayerKey, LogParser, LogParserOptions, DataLog};
+
+/// Maximum difference in seconds between two logs' timestamps for the same event, once their clocks are aligned
+const DEDUPE_WINDOW: i64 = 2;
+/// Maximum difference in seconds between two dives' start times for them to be treated as the same dive
+const DIVE_MATCH_WINDOW: i64 = 60;
+
+/// Seconds since the start of the log, from a line such as "0T23:17:51 70 I ..."
+pub fn line_time(text: &str) -> Option<i64> {
+    let (day, rest) = text.split_once('T')?;
+    let mut time = rest.get(..8)?.split(':').map(|p| p.parse::<i64>().ok());
+    let (h, m, s) = (time.next()??, time.next()??, time.next()??);
+    Some(((day.parse::<i64>().ok()? * 24 + h) * 60 + m) * 60 + s)
+}
+
+struct TimedEvent {
+    time: i64,
+    event: Event,
+}
+
+/// Everything from one StartDive up to the next, in a single log
+struct LogDive {
+    party: Option<String>,
+    start: TimedEvent,
+    events: Vec<TimedEvent>,
+}
+
+/// A log split up into the events before the first dive, and each dive
+struct SplitLog {
+    header: Vec<TimedEvent>,
+    dives: Vec<LogDive>,
+}
+
+impl SplitLog {
+    fn parse(log: &str, options: LogParserOptions) -> Self {
+        // Timestamps are only available from the line text
+        let mut parser = LogParser::with_options(options.keep_lines(true));
+        let mut split = SplitLog { header: Vec::new(), dives: Vec::new() };
+        let mut party = None;
+        let mut time = 0;
+
+        for line in log.lines() {
+            let Some(event) = parser.parse_line(line) else { continue };
+            // Lines without a timestamp are assumed to happen at the same time as the previous one
+            time = event.line().text.as_deref().and_then(line_time).unwrap_or(time);
+
+            if let Event::SetSelf(_, _, Some(id)) = &event {
+                party = Some(id.clone());
+            }
+            let event = TimedEvent { time, event };
+            match (&event.event, split.dives.last_mut()) {
+                (Event::StartDive(_), _) => split.dives.push(LogDive { party: party.clone(), start: event, events: Vec::new() }),
+                (_, Some(dive)) => dive.events.push(event),
+                (_, None) => split.header.push(event),
+            }
+        }
+        split
+    }
+}
+
+fn entity(entity: &mut Entity) -> Option<&mut PlayerData> {
+    match entity {
+        Entity::Player(p) => Some(p),
+        Entity::Id(_) => None,
+    }
+}
+
+/// Players in an event, so their keys can be made the same across logs
+fn players_mut(event: &mut Event) -> Vec<&mut PlayerData> {
+    match event {
+        Event::DamageDealt(_, d) => [Some(&mut d.source), entity(&mut d.target)].into_iter().flatten().collect(),
+        Event::DamageReceived(_, d) => [entity(&mut d.source), Some(&mut d.target)].into_iter().flatten().collect(),
+        Event::DamageOther(_, d) => [entity(&mut d.source), entity(&mut d.target)].into_iter().flatten().collect(),
+        Event::AddStatusEffect(_, d) => [entity(&mut d.source), entity(&mut d.target)].into_iter().flatten().collect(),
+        Event::OrbPickup(_, p) => vec![p],
+        _ => Vec::new(),
+    }
+}
+
+fn same_player(a: &PlayerData, b: &PlayerData) -> bool {
+    a.key == b.key
+}
+
+/// Entity handles are assigned by each client, so other units are never compared, only players by key
+fn same_entity(a: &Entity, b: &Entity) -> bool {
+    match (a, b) {
+        (Entity::Player(a), Entity::Player(b)) => same_player(a, b),
+        (Entity::Id(_), Entity::Id(_)) => true,
+        _ => false,
+    }
+}
+
+/// Whether two events from different logs describe the same thing, ignoring line info, party slots and entity handles
+fn same_event(a: &Event, b: &Event) -> bool {
+    match (a, b) {
+        (Event::DamageDealt(_, a), Event::DamageDealt(_, b)) =>
+            same_player(&a.source, &b.source) && same_entity(&a.target, &b.target)
+            && a.amount == b.amount && a.ability == b.ability && a.crit == b.crit && a.dodged == b.dodged,
+        (Event::DamageReceived(_, a), Event::DamageReceived(_, b)) =>
+            same_entity(&a.source, &b.source) && same_player(&a.target, &b.target)
+            && a.amount == b.amount && a.ability == b.ability && a.crit == b.crit && a.dodged == b.dodged,
+        (Event::DamageOther(_, a), Event::DamageOther(_, b)) =>
+            same_entity(&a.source, &b.source) && same_entity(&a.target, &b.target)
+            && a.amount == b.amount && a.ability == b.ability && a.crit == b.crit && a.dodged == b.dodged,
+        (Event::AddStatusEffect(_, a), Event::AddStatusEffect(_, b)) =>
+            same_entity(&a.source, &b.source) && same_entity(&a.target, &b.target)
+            && a.target_team == b.target_team && a.effectname == b.effectname && a.added == b.added && a.newvalue == b.newvalue,
+        (Event::OrbPickup(_, a), Event::OrbPickup(_, b)) => same_player(a, b),
+        (Event::StartCombat(_), Event::StartCombat(_))
+        | (Event::EndCombat(_), Event::EndCombat(_))
+        | (Event::NextTurn(_), Event::NextTurn(_))
+        | (Event::EndDive(_), Event::EndDive(_)) => true,
+        _ => false,
+    }
+}
+
+/// A dive being merged, events are kept sorted by (aligned) time
+struct MergedDive {
+    party: Option<String>,
+    start: TimedEvent,
+    /// Event, and which logs it was seen in
+    events: Vec<(TimedEvent, Vec<usize>)>,
+    /// Player name -> key from the first log that saw them, as keys contain handles which differ between clients
+    keys: HashMap<String, PlayerKey>,
+}
+
+impl MergedDive {
+    fn new(dive: LogDive, source: usize, offset: i64) -> Self {
+        let mut merged = Self {
+            party: dive.party,
+            start: TimedEvent { time: dive.start.time + offset, event: dive.start.event },
+            events: Vec::new(),
+            keys: HashMap::new(),
+        };
+        merged.add(dive.events, source, offset, false);
+        merged
+    }
+
+    /// Add events from a log, skipping any already seen in another log
+    fn add(&mut self, events: Vec<TimedEvent>, source: usize, offset: i64, dedupe: bool) {
+        for TimedEvent { time, mut event } in events {
+            let time = time + offset;
+            // Only the first log's view of who the player is, or which game version, is kept
+            if source != 0 && matches!(event, Event::SetSelf(..) | Event::GameVersion(..)) {
+                continue;
+            }
+            for player in players_mut(&mut event) {
+                player.key = self.keys.entry(player.name.clone()).or_insert_with(|| player.key.clone()).clone();
+            }
+
+            let from = self.events.partition_point(|(e, _)| e.time < time - DEDUPE_WINDOW);
+            let duplicate = if dedupe {
+                self.events[from..].iter_mut()
+                    .take_while(|(e, _)| e.time <= time + DEDUPE_WINDOW)
+                    .find(|(e, seen)| !seen.contains(&source) && same_event(&e.event, &event))
+            } else {
+                None
+            };
+            match duplicate {
+                Some((_, seen)) => seen.push(source),
+                None => {
+                    let index = self.events.partition_point(|(e, _)| e.time <= time);
+                    self.events.insert(index, (TimedEvent { time, event }, vec![source]));
+                }
+            }
+        }
+    }
+}
+
+/// Merge logs from several members of the same party into a single DataLog.
+/// Dives are matched by party id and start time, and events seen by more than one player are only counted once.
+/// The first log is used as the reference for clock alignment and the pov character.
+pub fn merge_logs(logs: &[&str], options: LogParserOptions) -> DataLog {
+    let mut logs = logs.iter().map(|log| SplitLog::parse(log, options.clone()));
+    let Some(reference) = logs.next() else { return DataLog::new() };
+
+    let header = reference.header;
+    let mut dives: Vec<MergedDive> = reference.dives.into_iter().map(|d| MergedDive::new(d, 0, 0)).collect();
+
+    for (source, log) in logs.enumerate().map(|(i, l)| (i + 1, l)) {
+        // Clocks may differ between players, so align on the first dive with a party in common
+        let offset = log.dives.iter().find_map(|dive| {
+            let party = dive.party.as_ref()?;
+            dives.iter().find(|m| m.party.as_ref() == Some(party)).map(|m| m.start.time - dive.start.time)
+        });
+        if offset.is_none() {
+            warn!("log {source} has no party in common with the first log, its dives will not be merged");
+        }
+
+        for dive in log.dives {
+            let matched = offset.and_then(|offset| dives.iter_mut().find(|m|
+                m.party.is_some() && m.party == dive.party && (m.start.time - (dive.start.time + offset)).abs() <= DIVE_MATCH_WINDOW
+            ));
+            match matched {
+                Some(merged) => merged.add(dive.events, source, offset.unwrap_or_default(), true),
+                None => dives.push(MergedDive::new(dive, source, offset.unwrap_or_default())),
+            }
+        }
+    }
+
+    dives.sort_by_key(|d| d.start.time);
+    let mut datalog = DataLog::new();
+    datalog.handle_events(header.into_iter().map(|e| e.event).collect());
+    for dive in dives {
+        datalog.handle_event(dive.start.event);
+        datalog.handle_events(dive.events.into_iter().map(|(e, _)| e.event).collect());
+    }
+    datalog
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const L_SET_SELF: &str = "0T00:44:47 45 I Joining hub - characterId: 00000000000, characterName: TestName, partyId: 392f1b98-4d51-4379-8624-72cce1bab72b";
+    const L_START_DIVE: &str = "0T00:45:00 80 I Party run start triggered - solo party: False";
+    const L_REGISTER_NAME: &str = "0T00:45:10 66 I TestPlayer (EntityHandle:22) is playing ability AbilityData-Flurry_AbilityData (Flurry my7gMbFo)";
+    const L_UNIT_CLASS: &str = "0T00:45:10 57 I Setting unit class for animation-UnitEntityHandle:(EntityHandle:22)-classType:C02";
+    const L_START_COMBAT: &str = "0T00:45:11 50 I [EventSystem] broadcasting EventOnCombatStarted-WorldStateChangeCombatStarted-CombatZoneHandle:(EntityHandle:68)-TriggeringInteractableHandle:(EntityHandle:69)";
+
+    fn damage(time: &str, amount: i64) -> String {
+        format!("0T{time} 70 I [EventSystem] broadcasting EventOnUnitDamaged-WorldStateChangeDamageUnit-TargetUnitHandle:(EntityHandle:78)-SourceEntityHandle:(EntityHandle:22)-TargetUnitTeam:Enemy-IsInActiveCombat:True-DamageAmount:{amount}-IsCriticalHit:False-WasDodged:False-ActionData:ActionData-Flurry_BaseDamage_Action (UPNE5APs)")
+    }
+
+    /// Give every entity in a log a different handle, as another client would
+    fn shift_handles(log: &str, by: i64) -> String {
+        let handles = regex::Regex::new(r"EntityHandle:(\d+)").unwrap();
+        handles.replace_all(log, |c: &regex::Captures| format!("EntityHandle:{}", c[1].parse::<i64>().unwrap() + by)).into_owned()
+    }
+
+    /// Shift every timestamp in a log by a number of hours, as if written by a client in another timezone
+    fn shift_hours(log: &str, hours: i64) -> String {
+        log.lines()
+            .map(|l| {
+                let hour: i64 = l[2..4].parse().unwrap();
+                format!("0T{:02}{}", hour + hours, &l[4..])
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    #[test]
+    fn merge_two_logs() {
+        let shared = [L_SET_SELF, L_START_DIVE, L_REGISTER_NAME, L_UNIT_CLASS, L_START_COMBAT].join("\n");
+        let first = [shared.clone(), damage("00:45:20", 10), damage("00:45:21", 10), damage("00:45:30", 5)].join("\n");
+        // Missed the 5 damage, but saw an extra 7 the first log didn't
+        let second = [shared, damage("00:45:20", 10), damage("00:45:22", 10), damage("00:45:40", 7)].join("\n");
+        let second = shift_handles(&shift_hours(&second, 3), 100);
+
+        let datalog = merge_logs(&[&first, &second], LogParserOptions::default());
+        assert_eq!(datalog.dives.len(), 1);
+        assert_eq!(datalog.dives[0].combats.len(), 1);
+        let players = datalog.dives[0].player_stats.iter().filter(|p| p.player_data.name == "TestPlayer").count();
+        assert_eq!(players, 1);
+        let player = datalog.dives[0].player_stats.get_by_name("TestPlayer").unwrap();
+        assert_eq!(player.total_damage_dealt, 10 + 10 + 5 + 7);
+        assert_eq!(datalog.pov.as_deref(), Some("TestName"));
+    }
+
+    #[test]
+    fn unmatched_dives_are_kept() {
+        let first = [L_SET_SELF, L_START_DIVE, L_REGISTER_NAME, &damage("00:45:20", 10)].join("\n");
+        let second = first.replace("392f1b98", "00000000");
+
+        let datalog = merge_logs(&[&first, &second], LogParserOptions::default());
+        assert_eq!(datalog.dives.len(), 2);
+    }
+}
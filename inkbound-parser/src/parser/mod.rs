@@ -1,12 +1,25 @@
 mod logparser;
 
-pub use logparser::LogParser;
+pub use logparser::{LogParser, LogParserOptions, ParserState};
+
+mod patterns;
+mod compat;
+mod diagnostics;
+pub use diagnostics::{ParserDiagnostics, PartialMatch};
+pub use compat::{GameVersion, GameVersionInfo, VersionRange};
+pub use patterns::{LinePatterns, PatternError, PatternFile, PatternDefs, PatternDef};
+pub(crate) use patterns::LineKind;
+
+mod party;
+pub use party::PlayerKey;
 
 mod playerstats;
 pub use playerstats::{PlayerStats, PlayerStatList};
 mod loggers;
 pub use loggers::*;
-use serde::Serialize;
+mod merge;
+pub use merge::{merge_logs, line_time};
+use serde::{Serialize, Deserialize};
 
 use crate::aspects::Aspect;
 
@@ -16,11 +29,16 @@ pub enum Entity {
     Player(PlayerData),
 }
 
-#[derive(PartialEq, Debug, Clone, Serialize)]
+#[derive(PartialEq, Debug, Clone, Serialize, Deserialize)]
 pub struct PlayerData {
     pub name: String,
     pub class: Aspect,
+    /// Entity handle, may change if the player reconnects
     pub id: i64,
+    /// Stable identity of the player
+    pub key: PlayerKey,
+    /// Position in the party, by first appearance in the dive
+    pub slot: usize,
 }
 
 #[derive(PartialEq, Debug, Clone)]
@@ -103,21 +121,70 @@ pub struct AddStatusEffectData {
     newvalue: i64,
 }
 
+/// Location of the log line an Event was parsed from
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct LineInfo {
+    /// Line number in the log, starting from 1
+    pub number: usize,
+    /// Raw text of the line, only retained if enabled via LogParserOptions
+    pub text: Option<String>,
+}
+
 #[derive(PartialEq, Debug, Clone)]
 pub enum Event {
-    StartDive(String),
-    EndDive(String),
-    StartCombat(String),
-    EndCombat(String),
-    /// line, name, data
-    DamageDealt(String, DamageDealtEventData),
-    /// line, name, data
-    DamageReceived(String, DamageReceivedEventData),
-    DamageOther(String, DamageEventData),
-    AddStatusEffect(String, AddStatusEffectData),
+    StartDive(LineInfo),
+    EndDive(LineInfo),
+    StartCombat(LineInfo),
+    EndCombat(LineInfo),
+    /// line, data
+    DamageDealt(LineInfo, DamageDealtEventData),
+    /// line, data
+    DamageReceived(LineInfo, DamageReceivedEventData),
+    DamageOther(LineInfo, DamageEventData),
+    AddStatusEffect(LineInfo, AddStatusEffectData),
     // RegisterPlayer(String, String, String),
-    NextTurn(String),
-    OrbPickup(String, PlayerData),
-    SetSelf(String, String),
+    NextTurn(LineInfo),
+    OrbPickup(LineInfo, PlayerData),
+    /// line, name, party id
+    SetSelf(LineInfo, String, Option<String>),
+    GameVersion(LineInfo, GameVersionInfo),
     // Unknown(String),
 }
+
+impl Event {
+    /// Line the event was parsed from
+    pub fn line(&self) -> &LineInfo {
+        match self {
+            Event::StartDive(line)
+            | Event::EndDive(line)
+            | Event::StartCombat(line)
+            | Event::EndCombat(line)
+            | Event::NextTurn(line)
+            | Event::DamageDealt(line, _)
+            | Event::DamageReceived(line, _)
+            | Event::DamageOther(line, _)
+            | Event::AddStatusEffect(line, _)
+            | Event::OrbPickup(line, _)
+            | Event::SetSelf(line, _, _)
+            | Event::GameVersion(line, _) => line,
+        }
+    }
+
+    /// Name of the event type, e.g. for counting events
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Event::StartDive(_) => "StartDive",
+            Event::EndDive(_) => "EndDive",
+            Event::StartCombat(_) => "StartCombat",
+            Event::EndCombat(_) => "EndCombat",
+            Event::DamageDealt(_, _) => "DamageDealt",
+            Event::DamageReceived(_, _) => "DamageReceived",
+            Event::DamageOther(_, _) => "DamageOther",
+            Event::AddStatusEffect(_, _) => "AddStatusEffect",
+            Event::NextTurn(_) => "NextTurn",
+            Event::OrbPickup(_, _) => "OrbPickup",
+            Event::SetSelf(_, _, _) => "SetSelf",
+            Event::GameVersion(_, _) => "GameVersion",
+        }
+    }
+}
@@ -0,0 +1,247 @@
+use std::collections::{HashMap, HashSet};
+
+use serde::{Serialize, Deserialize};
+
+use crate::aspects::Aspect;
+
+use super::{Entity, PlayerData};
+
+/// Stable identity of a player, independent of the entity handle they currently have.
+/// Uses the character id when the log provides one, otherwise the name and the first handle seen for the player in the dive.
+/// NOTE: not the aspect, as that may only be logged after the player's first action.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct PlayerKey(pub String);
+
+impl PlayerKey {
+    pub fn new(name: &str, handle: i64, character_id: Option<&str>) -> Self {
+        match character_id {
+            Some(character_id) => Self(character_id.to_string()),
+            None => Self(format!("{name} ({handle})")),
+        }
+    }
+}
+
+impl std::fmt::Display for PlayerKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Maps entity handles to players for the current dive.
+/// A player that reconnects gets a new handle, which is resolved back to the same PlayerKey and party slot.
+/// NOTE: two characters with the same name can only be told apart once their aspects are known, unless the log provides character ids.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub(crate) struct Party {
+    names: HashMap<i64, String>, // handle -> name
+    classes: HashMap<i64, Aspect>, // handle -> pre-translated Aspect
+    characters: HashMap<i64, String>, // handle -> character id, if the patterns capture one
+    #[serde(default)]
+    keys: HashMap<i64, PlayerKey>, // handle -> player, from when the handle is first resolved
+    /// Handles whose player was guessed by name alone, to check again once their class is known
+    #[serde(default)]
+    unsettled: HashSet<i64>,
+    slots: Vec<PlayerKey>, // Party slot order, by first appearance in the dive
+}
+
+impl Party {
+    pub(crate) fn register_name(&mut self, id: i64, name: String, character_id: Option<String>) {
+        // Handle reused by a different unit, forget whatever was known about the old one
+        if self.names.get(&id).is_some_and(|old| *old != name) {
+            self.classes.remove(&id);
+            self.characters.remove(&id);
+            self.keys.remove(&id);
+            self.unsettled.remove(&id);
+        }
+        if let Some(character_id) = character_id {
+            self.characters.insert(id, character_id);
+        }
+        self.names.insert(id, name);
+    }
+
+    pub(crate) fn set_class(&mut self, id: i64, class: Aspect) {
+        self.classes.insert(id, class);
+        // The guess by name may have been wrong, or can now be made
+        if self.unsettled.remove(&id) {
+            if let Some(name) = self.names.get(&id).cloned() {
+                self.keys.remove(&id);
+                self.key(id, &name);
+            }
+        }
+    }
+
+    /// Class of any handle a player has had
+    fn key_class(&self, key: &PlayerKey) -> Option<&Aspect> {
+        self.keys.iter()
+            .filter(|(_, k)| *k == key)
+            .find_map(|(handle, _)| self.classes.get(handle))
+    }
+
+    /// Get the player a handle belongs to, deciding it the first time the handle is seen.
+    /// A new handle with the name of a player already in the dive is most likely a reconnect
+    fn key(&mut self, id: i64, name: &str) -> PlayerKey {
+        if let Some(key) = self.keys.get(&id) {
+            return key.clone()
+        }
+        if let Some(character_id) = self.characters.get(&id) {
+            let key = PlayerKey::new(name, id, Some(character_id));
+            self.keys.insert(id, key.clone());
+            return key
+        }
+
+        let class = self.classes.get(&id);
+        let mut candidates: Vec<&PlayerKey> = self.keys.iter()
+            .filter(|(handle, _)| self.names.get(handle).is_some_and(|n| n == name) && !self.characters.contains_key(handle))
+            .map(|(_, key)| key)
+            .filter(|key| class.is_none() || self.key_class(key).is_none_or(|c| Some(c) == class))
+            .collect();
+        candidates.sort();
+        candidates.dedup();
+
+        let key = match candidates.as_slice() {
+            [] => PlayerKey::new(name, id, None),
+            [key] => (*key).clone(),
+            // Can't tell which, so treat it as a new player until its class is known
+            _ => PlayerKey::new(name, id, None),
+        };
+        if class.is_none() && !candidates.is_empty() {
+            self.unsettled.insert(id);
+        }
+        self.keys.insert(id, key.clone());
+        key
+    }
+
+    /// Class for a handle, falling back to another handle of the same player if this one hasn't been seen yet
+    fn class(&self, id: i64, key: &PlayerKey) -> Aspect {
+        self.classes.get(&id)
+            .or_else(|| self.key_class(key))
+            .cloned()
+            .unwrap_or_else(|| Aspect::Unknown(id.to_string()))
+    }
+
+    /// Get the party slot for a player, assigning the next free one if they are new
+    fn slot(&mut self, key: &PlayerKey) -> usize {
+        match self.slots.iter().position(|k| k == key) {
+            Some(slot) => slot,
+            None => {
+                self.slots.push(key.clone());
+                self.slots.len() - 1
+            }
+        }
+    }
+
+    /// Convert an entity handle to a player, if it belongs to one
+    pub(crate) fn resolve(&mut self, entity: Entity) -> Entity {
+        let id = match entity {
+            Entity::Player(_) => return entity,
+            Entity::Id(id) => id,
+        };
+        let name = match self.names.get(&id) {
+            Some(name) => name.clone(),
+            // Not a player
+            None => return entity,
+        };
+
+        let key = self.key(id, &name);
+        let class = self.class(id, &key);
+        let slot = self.slot(&key);
+
+        Entity::Player(PlayerData {
+            name,
+            class,
+            id,
+            key,
+            slot,
+        })
+    }
+
+    pub(crate) fn clear(&mut self) {
+        *self = Self::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn player(party: &mut Party, id: i64) -> PlayerData {
+        match party.resolve(Entity::Id(id)) {
+            Entity::Player(p) => p,
+            other => panic!("received {:?}", other),
+        }
+    }
+
+    #[test]
+    fn reconnect_and_collisions() {
+        let mut party = Party::default();
+        party.register_name(5, "Alice".to_string(), None);
+        party.set_class(5, Aspect::Weaver);
+        party.register_name(8, "Bob".to_string(), None);
+        party.set_class(8, Aspect::Obelisk);
+        // Same name, different aspect
+        party.register_name(9, "Alice".to_string(), None);
+        party.set_class(9, Aspect::Godkeeper);
+
+        assert_eq!(party.resolve(Entity::Id(70)), Entity::Id(70));
+        assert_eq!(player(&mut party, 8).slot, 0);
+        assert_eq!(player(&mut party, 5).slot, 1);
+        assert_eq!(player(&mut party, 9).slot, 2);
+        assert_ne!(player(&mut party, 5).key, player(&mut party, 9).key);
+
+        // Bob reconnects with a new handle, before his class is known
+        party.register_name(12, "Bob".to_string(), None);
+        let bob = player(&mut party, 12);
+        assert_eq!(bob.class, Aspect::Obelisk);
+        assert_eq!(bob.slot, 0);
+        assert_eq!(bob.key, player(&mut party, 8).key);
+
+        // Alice's name is ambiguous, so a new handle isn't remapped until its class is known
+        party.register_name(13, "Alice".to_string(), None);
+        assert_eq!(player(&mut party, 13).class, Aspect::Unknown("13".to_string()));
+        party.set_class(13, Aspect::Weaver);
+        assert_eq!(player(&mut party, 13).slot, 1);
+
+        // Handle reused by someone else
+        party.register_name(8, "Carol".to_string(), None);
+        assert_eq!(player(&mut party, 8).class, Aspect::Unknown("8".to_string()));
+    }
+
+    #[test]
+    fn class_after_first_action() {
+        let mut party = Party::default();
+        party.register_name(5, "Alice".to_string(), None);
+        let before = player(&mut party, 5);
+        assert_eq!(before.class, Aspect::Unknown("5".to_string()));
+
+        party.set_class(5, Aspect::Weaver);
+        let after = player(&mut party, 5);
+        assert_eq!(after.class, Aspect::Weaver);
+        assert_eq!(after.key, before.key);
+        assert_eq!(after.slot, before.slot);
+
+        // Another Alice whose class comes later is first guessed to be a reconnect, then told apart
+        party.register_name(9, "Alice".to_string(), None);
+        assert_eq!(player(&mut party, 9).key, before.key);
+        party.set_class(9, Aspect::Godkeeper);
+        let other = player(&mut party, 9);
+        assert_ne!(other.key, before.key);
+        assert_eq!(other.slot, 1);
+    }
+
+    #[test]
+    fn character_ids() {
+        let mut party = Party::default();
+        party.register_name(5, "Alice".to_string(), Some("111".to_string()));
+        party.set_class(5, Aspect::Weaver);
+        party.register_name(6, "Alice".to_string(), Some("222".to_string()));
+        party.set_class(6, Aspect::Weaver);
+
+        assert_eq!(player(&mut party, 5).key, PlayerKey("111".to_string()));
+        assert_eq!(player(&mut party, 6).key, PlayerKey("222".to_string()));
+
+        party.register_name(7, "Alice".to_string(), Some("222".to_string()));
+        let alice = player(&mut party, 7);
+        assert_eq!(alice.class, Aspect::Weaver);
+        assert_eq!(alice.slot, 1);
+    }
+}
@@ -0,0 +1,283 @@
+use std::sync::Arc;
+
+use regex::{Regex, RegexSet, SetMatches, Captures};
+use serde::Deserialize;
+
+
+use super::{GameVersion, VersionRange};
+
+static BUNDLED_PATTERNS: &str = include_str!("patterns.ron");
+
+lazy_static::lazy_static! {
+    static ref BUNDLED: Arc<LinePatterns> = Arc::new(
+        LinePatterns::from_ron(BUNDLED_PATTERNS).expect("bundled patterns failed to validate")
+    );
+}
+
+#[derive(Debug)]
+pub enum PatternError {
+    /// Pattern file could not be read
+    Io(String, std::io::Error),
+    /// Pattern file is not valid, or is missing a pattern
+    Parse(ron::error::SpannedError),
+    /// A pattern's regex failed to compile
+    Regex(&'static str, regex::Error),
+    /// A pattern's regex is missing a named capture group the parser needs
+    MissingGroup(&'static str, &'static str),
+    /// A pattern that extracts data was given no regex
+    MissingRegex(&'static str),
+    /// A pattern's keyword is empty, which would match every line
+    EmptyKeyword(&'static str),
+}
+
+impl std::fmt::Display for PatternError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PatternError::Io(path, e) => write!(f, "unable to read pattern file {path}: {e}"),
+            PatternError::Parse(e) => write!(f, "invalid pattern file: {e}"),
+            PatternError::Regex(name, e) => write!(f, "pattern `{name}` has an invalid regex: {e}"),
+            PatternError::MissingGroup(name, group) => write!(f, "pattern `{name}` is missing the capture group `(?<{group}>...)`"),
+            PatternError::MissingRegex(name) => write!(f, "pattern `{name}` requires a regex"),
+            PatternError::EmptyKeyword(name) => write!(f, "pattern `{name}` has an empty keyword"),
+        }
+    }
+}
+
+impl std::error::Error for PatternError {}
+
+/// Kinds of lines the parser cares about. Also the index of each pattern in LinePatterns.
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum LineKind {
+    Damage,
+    UnitClass,
+    RegisterName,
+    OrbPickup,
+    SetSelf,
+    AddStatusEffect,
+    StartDive,
+    StartCombat,
+    EndCombat,
+    NextTurn,
+    EndDive,
+}
+
+impl LineKind {
+    /// Name of the pattern for this kind of line, as used in pattern files
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            LineKind::Damage => "damage",
+            LineKind::UnitClass => "unit_class",
+            LineKind::RegisterName => "register_name",
+            LineKind::OrbPickup => "orb_pickup",
+            LineKind::SetSelf => "set_self",
+            LineKind::AddStatusEffect => "add_status_effect",
+            LineKind::StartDive => "start_dive",
+            LineKind::StartCombat => "start_combat",
+            LineKind::EndCombat => "end_combat",
+            LineKind::NextTurn => "next_turn",
+            LineKind::EndDive => "end_dive",
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct PatternDef {
+    /// Literal text that must appear in a line before the regex is tried
+    pub keyword: String,
+    /// Full pattern to match, optional for patterns that don't extract any data
+    #[serde(default)]
+    pub regex: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct PatternDefs {
+    pub damage: PatternDef,
+    pub unit_class: PatternDef,
+    pub register_name: PatternDef,
+    pub orb_pickup: PatternDef,
+    pub set_self: PatternDef,
+    pub add_status_effect: PatternDef,
+    pub start_dive: PatternDef,
+    pub start_combat: PatternDef,
+    pub end_combat: PatternDef,
+    pub next_turn: PatternDef,
+    pub end_dive: PatternDef,
+}
+
+/// Raw contents of a pattern file, before validation
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct PatternFile {
+    pub patterns: PatternDefs,
+    /// Regex to detect the game version from the log header, must contain a `version` group
+    #[serde(default)]
+    pub version_pattern: Option<String>,
+    /// Game versions these patterns are known to work with. Patterns without a range are used for any version.
+    #[serde(default)]
+    pub game_versions: Option<VersionRange>,
+}
+
+impl PatternDefs {
+    /// All pattern definitions in LineKind order, with their names and required capture groups
+    fn iter(&self) -> [(&'static str, &PatternDef, &'static [&'static str]); 11] {
+        [
+            ("damage", &self.damage, &["target", "source", "damage", "crit", "dodged", "ability"]),
+            ("unit_class", &self.unit_class, &["id", "class"]),
+            ("register_name", &self.register_name, &["name", "id"]),
+            ("orb_pickup", &self.orb_pickup, &["id"]),
+            ("set_self", &self.set_self, &["name"]),
+            ("add_status_effect", &self.add_status_effect, &["target", "source", "targetteam", "effectname", "added", "newvalue"]),
+            ("start_dive", &self.start_dive, &[]),
+            ("start_combat", &self.start_combat, &[]),
+            ("end_combat", &self.end_combat, &[]),
+            ("next_turn", &self.next_turn, &[]),
+            ("end_dive", &self.end_dive, &[]),
+        ]
+    }
+}
+
+/// Validated and compiled set of line patterns used by the LogParser
+#[derive(Debug, Clone)]
+pub struct LinePatterns {
+    keywords: RegexSet,
+    regexes: Vec<Option<Regex>>,
+    version: Option<Regex>,
+    versions: Option<VersionRange>,
+}
+
+impl LinePatterns {
+    /// The patterns bundled with this build
+    pub fn bundled() -> Arc<Self> {
+        BUNDLED.clone()
+    }
+
+    /// Load and validate a pattern file
+    pub fn from_file(path: &str) -> Result<Self, PatternError> {
+        let data = std::fs::read_to_string(path).map_err(|e| PatternError::Io(path.to_string(), e))?;
+        Self::from_ron(&data)
+    }
+
+    /// Parse and validate the contents of a pattern file
+    pub fn from_ron(data: &str) -> Result<Self, PatternError> {
+        let file: PatternFile = ron::from_str(data).map_err(PatternError::Parse)?;
+        Self::compile(file)
+    }
+
+    pub fn compile(file: PatternFile) -> Result<Self, PatternError> {
+        let defs = file.patterns.iter();
+
+        let mut regexes = Vec::with_capacity(defs.len());
+        for (name, def, groups) in defs.iter() {
+            if def.keyword.is_empty() {
+                return Err(PatternError::EmptyKeyword(name));
+            }
+
+            let regex = match &def.regex {
+                Some(regex) => Some(Regex::new(regex).map_err(|e| PatternError::Regex(name, e))?),
+                None if !groups.is_empty() => return Err(PatternError::MissingRegex(name)),
+                None => None,
+            };
+
+            if let Some(regex) = &regex {
+                if let Some(group) = groups.iter().find(|g| !regex.capture_names().any(|n| n == Some(**g))) {
+                    return Err(PatternError::MissingGroup(name, group));
+                }
+            }
+            regexes.push(regex);
+        }
+
+        let keywords = RegexSet::new(defs.iter().map(|(_, def, _)| regex::escape(&def.keyword)))
+            .expect("escaped keywords should always compile");
+
+        let version = match &file.version_pattern {
+            Some(regex) => {
+                let regex = Regex::new(regex).map_err(|e| PatternError::Regex("version_pattern", e))?;
+                if !regex.capture_names().any(|n| n == Some("version")) {
+                    return Err(PatternError::MissingGroup("version_pattern", "version"));
+                }
+                Some(regex)
+            },
+            None => None,
+        };
+
+        Ok(Self {
+            keywords,
+            regexes,
+            version,
+            versions: file.game_versions,
+        })
+    }
+
+    /// Check which pattern keywords appear in a line, in a single pass
+    pub(crate) fn keywords(&self, line: &str) -> SetMatches {
+        self.keywords.matches(line)
+    }
+
+    /// Run the full pattern for a kind of line
+    pub(crate) fn captures<'a>(&self, kind: LineKind, line: &'a str) -> Option<Captures<'a>> {
+        self.regexes[kind as usize].as_ref().and_then(|r| r.captures(line))
+    }
+
+    /// Check the full pattern for a kind of line, for patterns that may only have a keyword
+    pub(crate) fn is_match(&self, kind: LineKind, line: &str) -> bool {
+        self.regexes[kind as usize].as_ref().is_none_or(|r| r.is_match(line))
+    }
+
+    /// Whether any pattern fully matches a line, or the line contains the game version
+    pub(crate) fn recognizes(&self, line: &str) -> bool {
+        self.keywords(line).iter().any(|kind| self.regexes[kind].as_ref().is_none_or(|r| r.is_match(line)))
+            || self.detect_version(line).is_some()
+    }
+
+    /// The name in a line that names an entity, either one playing an ability or the player joining the hub
+    pub(crate) fn entity_name<'a>(&self, line: &'a str) -> Option<regex::Match<'a>> {
+        [LineKind::RegisterName, LineKind::SetSelf].into_iter()
+            .filter_map(|kind| self.captures(kind, line))
+            .find_map(|caps| caps.name("name"))
+    }
+
+    /// Try to extract the game version from a log header line
+    pub(crate) fn detect_version(&self, line: &str) -> Option<GameVersion> {
+        self.version.as_ref()
+            .and_then(|r| r.captures(line))
+            .map(|caps| GameVersion(caps["version"].to_string()))
+    }
+
+    /// Game versions these patterns are known to work with
+    pub fn versions(&self) -> Option<&VersionRange> {
+        self.versions.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bundled_patterns_validate() {
+        LinePatterns::from_ron(BUNDLED_PATTERNS).unwrap();
+    }
+
+    #[test]
+    fn pattern_errors_name_pattern() {
+        let broken = BUNDLED_PATTERNS.replace("(?<crit>True|False)", "(?<crit>True|False");
+        match LinePatterns::from_ron(&broken) {
+            Err(e @ PatternError::Regex("damage", _)) => assert!(e.to_string().contains("`damage`")),
+            other => panic!("received {:?}", other),
+        }
+
+        let broken = BUNDLED_PATTERNS.replace("(?<class>", "(?<klass>");
+        match LinePatterns::from_ron(&broken) {
+            Err(PatternError::MissingGroup("unit_class", "class")) => (),
+            other => panic!("received {:?}", other),
+        }
+
+        let broken = BUNDLED_PATTERNS.replace("keyword: \"ManaOrbPickup\",", "");
+        match LinePatterns::from_ron(&broken) {
+            Err(e @ PatternError::Parse(_)) => assert!(e.to_string().contains("keyword")),
+            other => panic!("received {:?}", other),
+        }
+    }
+}
@@ -1,10 +1,10 @@
 use std::collections::HashMap;
-use serde::Serialize;
+use serde::{Serialize, Serializer, Deserialize, Deserializer, ser::SerializeMap};
 
-use super::{DamageReceivedEventData, DamageDealtEventData, PlayerData, AddStatusEffectData};
+use super::{DamageReceivedEventData, DamageDealtEventData, PlayerData, PlayerKey, AddStatusEffectData};
 
 /// Ongoing Statistics for a particular Player
-#[derive(Debug, Serialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct PlayerStats {
     // pub name: String,
     // pub class: Option<String>,
@@ -34,18 +34,18 @@ impl PlayerStats {
         }
     }
 
-    pub fn apply_dealt_damage(&mut self, dmg: DamageDealtEventData) {
+    pub fn apply_dealt_damage(&mut self, dmg: &DamageDealtEventData) {
         // I don't love the clone here, but it at least prevents the bleh if/else
         self.skill_totals.entry(dmg.ability.clone()).and_modify(|total| *total += dmg.amount).or_insert(dmg.amount);
 
         if dmg.crit {
-            self.crit_totals.entry(dmg.ability).and_modify(|total| *total += dmg.amount).or_insert(dmg.amount);
+            self.crit_totals.entry(dmg.ability.clone()).and_modify(|total| *total += dmg.amount).or_insert(dmg.amount);
         }
         
         self.total_damage_dealt += dmg.amount;
     }
 
-    pub fn apply_received_damage(&mut self, dmg: DamageReceivedEventData) {
+    pub fn apply_received_damage(&mut self, dmg: &DamageReceivedEventData) {
         self.total_damage_received += dmg.amount;
     }
 
@@ -53,65 +53,94 @@ impl PlayerStats {
         self.orb_pickups += 1;
     }
 
-    pub fn apply_status_effects(&mut self, data: AddStatusEffectData) {
+    /// Copy of just the totals, without the per-skill and per-status breakdowns
+    pub fn summary(&self) -> Self {
+        Self {
+            player_data: self.player_data.clone(),
+            total_damage_dealt: self.total_damage_dealt,
+            total_damage_received: self.total_damage_received,
+            skill_totals: HashMap::new(),
+            crit_totals: HashMap::new(),
+            orb_pickups: self.orb_pickups,
+            status_applied: HashMap::new(),
+        }
+    }
+
+    pub fn apply_status_effects(&mut self, data: &AddStatusEffectData) {
         self.status_applied.entry(data.effectname.clone()).and_modify(|total| *total += data.added).or_insert(data.added);
     }
 }
 
-#[derive(Debug, Serialize, Clone)]
+/// Stats for each player, in party slot order
+#[derive(Debug, Clone)]
 pub struct PlayerStatList {
-    #[serde(flatten)]
-    pub player_stats: HashMap<String, PlayerStats>,
+    pub player_stats: Vec<PlayerStats>,
 }
 
 impl PlayerStatList {
     pub fn new() -> Self {
         Self {
-            player_stats: HashMap::new(),
+            player_stats: Vec::new(),
         }
     }
 
-    // TODO: Remove the clones in these two functions
-    pub fn apply_dealt_damage(&mut self, dmg: DamageDealtEventData) {
-        let name = &dmg.source.name;
-        let player = if let Some(player) = self.player_stats.get_mut(name) {
-            player
-        } else {
-            self.player_stats.insert(name.clone(), PlayerStats::new(dmg.source.clone()));
-            self.player_stats.get_mut(name).unwrap()
+    /// Get the stats for a player, adding them in their party slot if not present
+    fn entry(&mut self, player: &PlayerData) -> &mut PlayerStats {
+        let index = match self.player_stats.iter().position(|p| p.player_data.key == player.key) {
+            Some(index) => index,
+            None => {
+                let index = self.player_stats.partition_point(|p| p.player_data.slot <= player.slot);
+                self.player_stats.insert(index, PlayerStats::new(player.clone()));
+                index
+            }
         };
+        &mut self.player_stats[index]
+    }
 
-        player.apply_dealt_damage(dmg);
+    pub fn get(&self, key: &PlayerKey) -> Option<&PlayerStats> {
+        self.player_stats.iter().find(|p| p.player_data.key == *key)
     }
 
-    pub fn apply_received_damage(&mut self, dmg: DamageReceivedEventData) {
-        let name = &dmg.target.name;
-        let player = if let Some(player) = self.player_stats.get_mut(name) {
-            player
-        } else {
-            self.player_stats.insert(name.clone(), PlayerStats::new(dmg.target.clone()));
-            self.player_stats.get_mut(name).unwrap()
-        };
+    /// First player with a given name, e.g. for the pov character
+    pub fn get_by_name(&self, name: &str) -> Option<&PlayerStats> {
+        self.player_stats.iter().find(|p| p.player_data.name == name)
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, PlayerStats> {
+        self.player_stats.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.player_stats.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.player_stats.is_empty()
+    }
+
+    /// Copy of just each player's totals, see `PlayerStats::summary`
+    pub fn summary(&self) -> Self {
+        Self {
+            player_stats: self.player_stats.iter().map(PlayerStats::summary).collect(),
+        }
+    }
+
+    pub fn apply_dealt_damage(&mut self, dmg: &DamageDealtEventData) {
+        self.entry(&dmg.source).apply_dealt_damage(dmg);
+    }
 
-        player.apply_received_damage(dmg);
+    pub fn apply_received_damage(&mut self, dmg: &DamageReceivedEventData) {
+        self.entry(&dmg.target).apply_received_damage(dmg);
     }
 
-    pub fn apply_orb_pickup(&mut self, player: PlayerData) {
-        self.player_stats.entry(player.name.clone())
-            .and_modify(|e| e.increment_orbs())
-            .or_insert(PlayerStats::new(player));
+    pub fn apply_orb_pickup(&mut self, player: &PlayerData) {
+        self.entry(player).increment_orbs();
     }
 
-    pub fn apply_status_effects(&mut self, data: AddStatusEffectData) {
-        // ugh this clone though
-        match data.clone().source {
+    pub fn apply_status_effects(&mut self, data: &AddStatusEffectData) {
+        match &data.source {
             super::Entity::Id(_) => (), // Only bother if it was a player
-            super::Entity::Player(player) => {
-                self.player_stats.entry(player.name.clone())
-                    .and_modify(|p| p.apply_status_effects(data))
-                    .or_insert(PlayerStats::new(player.clone()))
-                ;
-            }
+            super::Entity::Player(player) => self.entry(player).apply_status_effects(data),
         };
     }
 
@@ -128,6 +157,33 @@ impl PlayerStatList {
     // }
 }
 
+// Serialized as a map of name -> PlayerStats, in party slot order.
+//  Players sharing a name are told apart by their PlayerKey instead
+impl Serialize for PlayerStatList {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.player_stats.len()))?;
+        for (index, stats) in self.player_stats.iter().enumerate() {
+            let name = &stats.player_data.name;
+            if self.player_stats[..index].iter().any(|other| other.player_data.name == *name) {
+                map.serialize_entry(&stats.player_data.key.0, stats)?;
+            } else {
+                map.serialize_entry(name, stats)?;
+            }
+        }
+        map.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for PlayerStatList {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        // Keys are only for readability, each entry has its own player_data
+        let map = std::collections::BTreeMap::<String, PlayerStats>::deserialize(deserializer)?;
+        let mut player_stats: Vec<PlayerStats> = map.into_values().collect();
+        player_stats.sort_by_key(|p| p.player_data.slot);
+        Ok(Self { player_stats })
+    }
+}
+
 impl Default for PlayerStatList {
     fn default() -> Self {
         Self::new()
@@ -0,0 +1,221 @@
+use std::{collections::{HashMap, HashSet}, sync::Arc};
+
+use regex::{Regex, Captures};
+
+use crate::parser::{LinePatterns, LineKind};
+
+lazy_static::lazy_static! {
+    static ref CHARACTER_ID: Regex = Regex::new(r"characterId: (?<id>\d+)").unwrap();
+    static ref PARTY_ID: Regex = Regex::new(r"partyId: (?<id>[\w-]+)").unwrap();
+    // Home directories on windows, linux and mac, including escaped windows paths
+    static ref USER_PATH: Regex = Regex::new(r"(?i)(?<prefix>[a-z]:(?:\\\\|\\|/)Users(?:\\\\|\\|/)|/home/|/Users/)(?<user>[^\\/\s]+)").unwrap();
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct SanitizeOptions {
+    drop_unrecognized: bool,
+    patterns: Option<Arc<LinePatterns>>,
+}
+
+impl SanitizeOptions {
+    /// Drop any lines that the parser's patterns don't recognize, which also makes the output much smaller
+    pub fn drop_unrecognized(self, drop_unrecognized: bool) -> Self {
+        Self {
+            drop_unrecognized,
+            ..self
+        }
+    }
+
+    /// Patterns used to find player names and recognized lines, instead of the bundled patterns
+    pub fn patterns(self, patterns: Arc<LinePatterns>) -> Self {
+        Self {
+            patterns: Some(patterns),
+            ..self
+        }
+    }
+}
+
+/// Consistent replacements for one kind of identifier, numbered in order of first appearance
+#[derive(Debug, Default)]
+struct Replacements {
+    map: HashMap<String, String>,
+}
+
+impl Replacements {
+    fn insert(&mut self, original: &str, replacement: impl FnOnce(usize) -> String) {
+        let next = self.map.len() + 1;
+        self.map.entry(original.to_string()).or_insert_with(|| replacement(next));
+    }
+
+    fn get<'a>(&'a self, original: &'a str) -> &'a str {
+        self.map.get(original).map(String::as_str).unwrap_or(original)
+    }
+}
+
+/// Anonymizes logs so they can be shared, replacing player names, character ids, party ids and user paths.
+/// Replacements are consistent within a log, so the sanitized log still parses to the same stats.
+/// Players are the character joining the hub, and any entity given a class, monsters keep their names.
+/// NOTE: in lines the parser doesn't recognize, such as chat, player names are replaced wherever they appear as a word.
+pub struct Sanitizer {
+    options: SanitizeOptions,
+    patterns: Arc<LinePatterns>,
+    /// Entity handle -> name, for every entity seen playing an ability
+    entities: HashMap<String, String>,
+    /// Entity handles given a class, which only players have
+    classed: HashSet<String>,
+    names: Replacements,
+    characters: Replacements,
+    parties: Replacements,
+}
+
+impl Sanitizer {
+    pub fn new(options: SanitizeOptions) -> Self {
+        Self {
+            patterns: options.patterns.clone().unwrap_or_else(LinePatterns::bundled),
+            options,
+            entities: HashMap::new(),
+            classed: HashSet::new(),
+            names: Replacements::default(),
+            characters: Replacements::default(),
+            parties: Replacements::default(),
+        }
+    }
+
+    /// First pass, find every identifier that needs replacing.
+    /// Needs to see the whole log first, as names can appear before the line that identifies them as a player.
+    fn collect(&mut self, line: &str) {
+        if let Some(caps) = self.patterns.captures(LineKind::SetSelf, line) {
+            self.names.insert(&caps["name"], |n| format!("Player{n}"));
+        }
+        // A player's name and class can be logged in either order
+        if let Some(caps) = self.patterns.captures(LineKind::RegisterName, line) {
+            if self.classed.contains(&caps["id"]) {
+                self.names.insert(&caps["name"], |n| format!("Player{n}"));
+            }
+            self.entities.insert(caps["id"].to_string(), caps["name"].to_string());
+        }
+        if let Some(caps) = self.patterns.captures(LineKind::UnitClass, line) {
+            if let Some(name) = self.entities.get(&caps["id"]) {
+                self.names.insert(name, |n| format!("Player{n}"));
+            }
+            self.classed.insert(caps["id"].to_string());
+        }
+        for caps in CHARACTER_ID.captures_iter(line) {
+            let id = &caps["id"];
+            self.characters.insert(id, |n| format!("{n:0>width$}", width = id.len()));
+        }
+        for caps in PARTY_ID.captures_iter(line) {
+            self.parties.insert(&caps["id"], |n| format!("00000000-0000-0000-0000-{n:012}"));
+        }
+    }
+
+    /// Replace the name in a recognized line, only where the patterns expect one
+    fn replace_entity_name<'a>(&self, line: &'a str) -> std::borrow::Cow<'a, str> {
+        match self.patterns.entity_name(line) {
+            Some(name) if self.names.map.contains_key(name.as_str()) =>
+                format!("{}{}{}", &line[..name.start()], self.names.get(name.as_str()), &line[name.end()..]).into(),
+            _ => line.into(),
+        }
+    }
+
+    /// Matches any collected name as a whole word, longest first so that names containing other names are replaced whole
+    fn names_regex(&self) -> Option<Regex> {
+        let mut names: Vec<&String> = self.names.map.keys().collect();
+        if names.is_empty() {
+            return None;
+        }
+        names.sort_by(|a, b| b.len().cmp(&a.len()).then(a.cmp(b)));
+        let names: Vec<String> = names.into_iter().map(|n| regex::escape(n)).collect();
+        Some(Regex::new(&format!(r"\b(?:{})\b", names.join("|"))).expect("escaped names should always compile"))
+    }
+
+    /// Sanitize a full log
+    pub fn sanitize(&mut self, log: &str) -> String {
+        log.lines().for_each(|line| self.collect(line));
+        let names = self.names_regex();
+
+        let mut out = String::with_capacity(log.len());
+        for line in log.lines() {
+            let recognized = self.patterns.recognizes(line);
+            if self.options.drop_unrecognized && !recognized {
+                continue;
+            }
+
+            let line = match &names {
+                _ if recognized => self.replace_entity_name(line),
+                Some(names) => names.replace_all(line, |caps: &Captures| self.names.get(&caps[0]).to_string()),
+                None => line.into(),
+            };
+            let line = CHARACTER_ID.replace_all(&line, |caps: &Captures| format!("characterId: {}", self.characters.get(&caps["id"])));
+            let line = PARTY_ID.replace_all(&line, |caps: &Captures| format!("partyId: {}", self.parties.get(&caps["id"])));
+            let line = USER_PATH.replace_all(&line, "${prefix}user");
+
+            out.push_str(&line);
+            out.push('\n');
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::{LogParser, DataLog};
+
+    const LOG: &str = "\
+0T00:44:40 10 I Loading save from C:\\Users\\RealName\\AppData\\LocalLow\\Shiny Shoe\\Inkbound\\save.dat
+0T00:44:47 45 I Joining hub - characterId: 12345678901, characterName: SelfName, partyId: 392f1b98-4d51-4379-8624-72cce1bab72b
+0T00:45:00 80 I Party run start triggered - solo party: False
+0T00:45:09 12 I Chat message from Friend: hi SelfName
+0T00:45:10 66 I Friend (EntityHandle:22) is playing ability AbilityData-Flurry_AbilityData (Flurry my7gMbFo)
+0T00:45:10 57 I Setting unit class for animation-UnitEntityHandle:(EntityHandle:22)-classType:C02
+0T00:45:11 66 I SelfName (EntityHandle:23) is playing ability AbilityData-Flurry_AbilityData (Flurry my7gMbFo)
+0T00:45:20 70 I [EventSystem] broadcasting EventOnUnitDamaged-WorldStateChangeDamageUnit-TargetUnitHandle:(EntityHandle:78)-SourceEntityHandle:(EntityHandle:22)-TargetUnitTeam:Enemy-IsInActiveCombat:True-DamageAmount:25-IsCriticalHit:False-WasDodged:False-ActionData:ActionData-Flurry_BaseDamage_Action (UPNE5APs)
+";
+
+    fn parse(log: &str) -> String {
+        let mut parser = LogParser::new();
+        let mut datalog = DataLog::new();
+        datalog.handle_events(parser.parse_lines(&log.lines().collect::<Vec<&str>>()));
+        serde_json::to_string(&datalog).unwrap()
+    }
+
+    #[test]
+    fn sanitize_log() {
+        let sanitized = Sanitizer::new(SanitizeOptions::default()).sanitize(LOG);
+        for secret in ["RealName", "SelfName", "Friend", "12345678901", "392f1b98"] {
+            assert!(!sanitized.contains(secret), "{secret} was not sanitized:\n{sanitized}");
+        }
+        assert!(sanitized.contains("C:\\Users\\user\\AppData"));
+        assert!(sanitized.contains("Chat message from Player2: hi Player1"));
+        assert!(sanitized.contains("characterId: 00000000001"));
+        assert_eq!(sanitized.lines().count(), LOG.lines().count());
+
+        // Same stats, just with different names
+        let expected = parse(LOG).replace("SelfName", "Player1").replace("Friend", "Player2")
+            .replace("392f1b98-4d51-4379-8624-72cce1bab72b", "00000000-0000-0000-0000-000000000001");
+        assert_eq!(parse(&sanitized), expected);
+    }
+
+    #[test]
+    fn only_players_renamed() {
+        // A player named like their ability, and a monster that isn't given a class
+        let log = LOG.replace("Friend", "Flurry") + "\
+0T00:45:21 66 I Shade (EntityHandle:78) is playing ability AbilityData-Flurry_AbilityData (Flurry my7gMbFo)
+0T00:45:22 12 I Chat message from Flurry: Shade used Flurry
+";
+        let sanitized = Sanitizer::new(SanitizeOptions::default()).sanitize(&log);
+        assert!(sanitized.contains("Player2 (EntityHandle:22) is playing ability AbilityData-Flurry_AbilityData (Flurry my7gMbFo)"));
+        assert!(sanitized.contains("Shade (EntityHandle:78) is playing ability AbilityData-Flurry_AbilityData (Flurry my7gMbFo)"));
+        assert!(sanitized.contains("ActionData-Flurry_BaseDamage_Action"));
+        assert!(sanitized.contains("Chat message from Player2: Shade used Player2"));
+    }
+
+    #[test]
+    fn drop_unrecognized() {
+        let sanitized = Sanitizer::new(SanitizeOptions::default().drop_unrecognized(true)).sanitize(LOG);
+        assert!(!sanitized.contains("Loading save"));
+        assert!(!sanitized.contains("Chat message"));
+        assert_eq!(sanitized.lines().count(), 6);
+    }
+}
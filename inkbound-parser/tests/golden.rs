@@ -0,0 +1,66 @@
+//! Golden-file regression tests.
+//!
+//! Every `*.log` under `tests/fixtures` is run through the LogParser and DataLog, and the serialized result
+//! is compared against the `.json` file of the same name. After an intended change to parsing or aggregation,
+//! rewrite the expected outputs with `BLESS=1 cargo test --test golden` and review the diff.
+//!
+//! New fixtures should be anonymized first with `inkbound-dps --sanitize <FILE> --drop-unrecognized`.
+
+use std::path::{Path, PathBuf};
+
+use inkbound_parser::{parse_log_to_json, parser::LogParserOptions};
+
+const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+
+fn blessing() -> bool {
+    std::env::var("BLESS").is_ok_and(|v| !v.is_empty() && v != "0")
+}
+
+/// Parse a log, and pretty print it with sorted keys so the output is stable and diffs nicely
+fn render(log: &Path) -> String {
+    let json = parse_log_to_json(&log.to_string_lossy().to_string(), LogParserOptions::default()).unwrap();
+    let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+    serde_json::to_string_pretty(&value).unwrap() + "\n"
+}
+
+/// Short description of where two outputs first differ
+fn first_difference(expected: &str, actual: &str) -> String {
+    let line = expected.lines().zip(actual.lines())
+        .position(|(e, a)| e != a)
+        .unwrap_or(expected.lines().count().min(actual.lines().count()));
+    format!(
+        "first difference at line {}:\n  expected: {}\n  actual:   {}",
+        line + 1,
+        expected.lines().nth(line).unwrap_or("<end of file>"),
+        actual.lines().nth(line).unwrap_or("<end of file>"),
+    )
+}
+
+#[test]
+fn golden_files() {
+    let mut logs: Vec<PathBuf> = std::fs::read_dir(FIXTURES).unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "log"))
+        .collect();
+    logs.sort();
+    assert!(!logs.is_empty(), "no fixtures found in {FIXTURES}");
+
+    let mut failures = Vec::new();
+    for log in logs.iter() {
+        let golden = log.with_extension("json");
+        let actual = render(log);
+
+        if blessing() {
+            std::fs::write(&golden, &actual).unwrap();
+            continue;
+        }
+
+        match std::fs::read_to_string(&golden) {
+            Ok(expected) if expected == actual => (),
+            Ok(expected) => failures.push(format!("{}: output changed, {}", log.display(), first_difference(&expected, &actual))),
+            Err(e) => failures.push(format!("{}: unable to read {}: {e}", log.display(), golden.display())),
+        }
+    }
+
+    assert!(failures.is_empty(), "{}\n\nIf these changes are intended, rerun with BLESS=1 and review the diff.", failures.join("\n\n"));
+}
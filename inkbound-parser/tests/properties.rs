@@ -0,0 +1,64 @@
+//! Property tests for the parser.
+//!
+//! Logs are written by the game and may be cut off or corrupted, the parser must never panic on them.
+//! Reading a log in arbitrary chunks is covered by logreader's property tests.
+
+use inkbound_parser::{
+    generator::{LogGenerator, GeneratorOptions},
+    parser::{LogParser, DataLog},
+};
+use proptest::prelude::*;
+
+fn generated_log(seed: u64) -> String {
+    let options = GeneratorOptions::default()
+        .seed(seed)
+        .dives(2)
+        .combats(2)
+        .turns(3)
+        .noise(1);
+    LogGenerator::new(options).generate()
+}
+
+fn parse(lines: &[&str]) -> DataLog {
+    let mut datalog = DataLog::new();
+    datalog.handle_events(LogParser::new().parse_lines(lines));
+    datalog
+}
+
+/// Replace part of a line with arbitrary text, to reach the capture groups with unexpected values
+fn mutate(line: &str, at: usize, len: usize, with: &str) -> String {
+    let mut at = at % (line.len() + 1);
+    while !line.is_char_boundary(at) {
+        at -= 1;
+    }
+    let mut end = (at + len).min(line.len());
+    while !line.is_char_boundary(end) {
+        end += 1;
+    }
+    format!("{}{with}{}", &line[..at], &line[end..])
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    #[test]
+    fn arbitrary_lines_do_not_panic(lines in prop::collection::vec(".*", 0..20)) {
+        let lines: Vec<&str> = lines.iter().map(String::as_str).collect();
+        parse(&lines);
+    }
+
+    #[test]
+    fn mutated_lines_do_not_panic(
+        seed in any::<u64>(),
+        mutations in prop::collection::vec((any::<usize>(), any::<usize>(), 0..8usize, "[0-9]{0,30}|\\PC{0,8}"), 1..50),
+    ) {
+        let log = generated_log(seed);
+        let mut lines: Vec<String> = log.lines().map(str::to_string).collect();
+        for (line, at, len, with) in mutations {
+            let index = line % lines.len();
+            lines[index] = mutate(&lines[index], at, len, &with);
+        }
+        let lines: Vec<&str> = lines.iter().map(String::as_str).collect();
+        parse(&lines);
+    }
+}
@@ -0,0 +1,165 @@
+use std::{
+    sync::{
+        Arc,
+        RwLock,
+        atomic::{AtomicU64, Ordering},
+        mpsc::{SyncSender, Receiver, TrySendError, RecvError, TryRecvError, RecvTimeoutError},
+    },
+    time::Duration,
+};
+
+use inkbound_parser::parser::Event;
+
+/// What to do with new events when a subscriber's queue is full
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Overflow {
+    /// Drop the events for this subscriber only, counting them in `EventReceiver::lagged`
+    #[default]
+    Drop,
+    /// Wait for the subscriber to catch up. This slows down reading for everyone, including the DataLog,
+    ///  and stopping the LogReader waits on it too, so the subscriber must keep receiving until it drops the receiver
+    Block,
+}
+
+struct Subscriber {
+    sender: SyncSender<Event>,
+    overflow: Overflow,
+    lagged: Arc<AtomicU64>,
+}
+
+/// Receiving end of a subscription to the events parsed by a LogReader.
+/// The subscription ends when this is dropped.
+pub struct EventReceiver {
+    receiver: Receiver<Event>,
+    lagged: Arc<AtomicU64>,
+}
+
+impl EventReceiver {
+    /// Wait for the next event. Errors once the LogReader is dropped
+    pub fn recv(&self) -> Result<Event, RecvError> {
+        self.receiver.recv()
+    }
+
+    pub fn try_recv(&self) -> Result<Event, TryRecvError> {
+        self.receiver.try_recv()
+    }
+
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<Event, RecvTimeoutError> {
+        self.receiver.recv_timeout(timeout)
+    }
+
+    /// All events currently queued, without waiting
+    pub fn try_iter(&self) -> impl Iterator<Item = Event> + '_ {
+        self.receiver.try_iter()
+    }
+
+    /// Number of events dropped so far because this subscriber fell behind, see `Overflow::Drop`
+    pub fn lagged(&self) -> u64 {
+        self.lagged.load(Ordering::Relaxed)
+    }
+}
+
+type EventCallback = Box<dyn Fn(&Event) + Send + Sync>;
+
+/// Fans out parsed events to any number of subscribers and callbacks
+#[derive(Default)]
+pub(crate) struct EventBroadcast {
+    subscribers: RwLock<Vec<Arc<Subscriber>>>,
+    callbacks: RwLock<Vec<EventCallback>>,
+}
+
+impl EventBroadcast {
+    pub(crate) fn subscribe(&self, capacity: usize, overflow: Overflow) -> EventReceiver {
+        let (sender, receiver) = std::sync::mpsc::sync_channel(capacity);
+        let lagged = Arc::new(AtomicU64::new(0));
+        self.subscribers.write().unwrap().push(Arc::new(Subscriber { sender, overflow, lagged: lagged.clone() }));
+        EventReceiver { receiver, lagged }
+    }
+
+    pub(crate) fn on_event(&self, callback: impl Fn(&Event) + Send + Sync + 'static) {
+        self.callbacks.write().unwrap().push(Box::new(callback));
+    }
+
+    /// Send an event to everyone, dropping any subscribers that have gone away
+    pub(crate) fn send(&self, event: &Event) {
+        for callback in self.callbacks.read().unwrap().iter() {
+            callback(event);
+        }
+
+        // Send without holding the lock, so a blocked subscriber doesn't hold up new subscriptions
+        let subscribers = self.subscribers.read().unwrap().clone();
+        let disconnected: Vec<Arc<Subscriber>> = subscribers.into_iter().filter(|subscriber| match subscriber.overflow {
+            Overflow::Drop => match subscriber.sender.try_send(event.clone()) {
+                Ok(()) => false,
+                Err(TrySendError::Full(_)) => {
+                    subscriber.lagged.fetch_add(1, Ordering::Relaxed);
+                    false
+                },
+                Err(TrySendError::Disconnected(_)) => true,
+            },
+            Overflow::Block => subscriber.sender.send(event.clone()).is_err(),
+        }).collect();
+
+        if !disconnected.is_empty() {
+            self.subscribers.write().unwrap().retain(|subscriber| !disconnected.iter().any(|d| Arc::ptr_eq(d, subscriber)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use inkbound_parser::parser::LineInfo;
+
+    fn event(number: usize) -> Event {
+        Event::NextTurn(LineInfo { number, text: None })
+    }
+
+    #[test]
+    fn broadcast() {
+        let broadcast = EventBroadcast::default();
+        let slow = broadcast.subscribe(2, Overflow::Drop);
+        let fast = broadcast.subscribe(10, Overflow::Drop);
+        let dropped = broadcast.subscribe(10, Overflow::Drop);
+        drop(dropped);
+
+        let seen = Arc::new(AtomicU64::new(0));
+        let counter = seen.clone();
+        broadcast.on_event(move |_| { counter.fetch_add(1, Ordering::Relaxed); });
+
+        (1..=3).for_each(|n| broadcast.send(&event(n)));
+
+        assert_eq!(slow.try_iter().collect::<Vec<Event>>(), vec![event(1), event(2)]);
+        assert_eq!(slow.lagged(), 1);
+        assert_eq!(fast.try_iter().count(), 3);
+        assert_eq!(fast.lagged(), 0);
+        assert_eq!(seen.load(Ordering::Relaxed), 3);
+        assert_eq!(broadcast.subscribers.read().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn subscribe_while_blocked() {
+        let broadcast = Arc::new(EventBroadcast::default());
+        let blocking = broadcast.subscribe(1, Overflow::Block);
+
+        let sender = broadcast.clone();
+        let sending = std::thread::spawn(move || (1..=3).for_each(|n| sender.send(&event(n))));
+        // Give the sender time to fill the queue and block
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(!sending.is_finished());
+
+        let (done, subscribed) = std::sync::mpsc::channel();
+        let subscriber = broadcast.clone();
+        std::thread::spawn(move || {
+            let receiver = subscriber.subscribe(10, Overflow::Drop);
+            done.send(()).unwrap();
+            drop(receiver);
+        });
+        assert!(subscribed.recv_timeout(Duration::from_secs(5)).is_ok());
+
+        assert_eq!(blocking.try_recv(), Ok(event(1)));
+        assert_eq!(blocking.recv(), Ok(event(2)));
+        assert_eq!(blocking.recv(), Ok(event(3)));
+        sending.join().unwrap();
+    }
+}
@@ -0,0 +1,70 @@
+use std::{
+    borrow::Cow,
+    path::Path,
+};
+
+use inkbound_parser::parser::{DataLog, ParserState};
+use serde::{Serialize, Deserialize};
+
+use crate::source::SourcePosition;
+
+/// Bumped whenever the format changes, older checkpoints are then ignored
+const CHECKPOINT_VERSION: u32 = 3;
+
+/// Everything needed to continue reading a log without parsing it again from the start
+#[derive(Serialize, Deserialize)]
+pub(crate) struct Checkpoint<'a> {
+    version: u32,
+    /// Description of the source, so a checkpoint for one log isn't used for another
+    pub(crate) source: String,
+    pub(crate) position: SourcePosition,
+    pub(crate) parser: ParserState,
+    pub(crate) datalog: Cow<'a, DataLog>,
+}
+
+impl<'a> Checkpoint<'a> {
+    pub(crate) fn new(source: String, position: SourcePosition, parser: ParserState, datalog: &'a DataLog) -> Self {
+        Self {
+            version: CHECKPOINT_VERSION,
+            source,
+            position,
+            parser,
+            datalog: Cow::Borrowed(datalog),
+        }
+    }
+
+    /// Write the checkpoint, replacing any previous one only once it's complete
+    pub(crate) fn save(&self, path: &Path) -> std::io::Result<()> {
+        let partial = path.with_extension("partial");
+        let mut file = std::io::BufWriter::new(std::fs::File::create(&partial)?);
+        serde_json::to_writer(&mut file, self)?;
+        // Dropping the writer would ignore any error writing out the rest of the buffer
+        file.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        std::fs::rename(partial, path)
+    }
+}
+
+impl Checkpoint<'static> {
+    /// Load a checkpoint, if there is a usable one
+    pub(crate) fn load(path: &Path) -> Option<Self> {
+        let file = match std::fs::File::open(path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return None,
+            Err(e) => {
+                log::warn!("unable to open checkpoint {}: {e}", path.display());
+                return None
+            },
+        };
+        match serde_json::from_reader::<_, Self>(std::io::BufReader::new(file)) {
+            Ok(checkpoint) if checkpoint.version == CHECKPOINT_VERSION => Some(checkpoint),
+            Ok(checkpoint) => {
+                log::info!("ignoring checkpoint {} from version {}", path.display(), checkpoint.version);
+                None
+            },
+            Err(e) => {
+                log::warn!("unable to read checkpoint {}: {e}", path.display());
+                None
+            },
+        }
+    }
+}
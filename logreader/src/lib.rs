@@ -1,2 +1,11 @@
 mod logreader;
+mod broadcast;
+mod source;
+mod remote;
+mod replay;
+mod checkpoint;
 pub use logreader::*;
+pub use broadcast::{EventReceiver, Overflow};
+pub use source::{LogSource, SourceLine, SourcePosition, Wake, FileSource, WatcherBackend, StreamSource, MemorySource};
+pub use remote::{Forwarder, RemoteSource};
+pub use replay::{ReplaySource, ReplayControl};
@@ -1,30 +1,33 @@
 use std::{
+    collections::BTreeMap,
     sync::{
         Arc,
         RwLock,
-        mpsc::{Sender, Receiver},
-        atomic::Ordering
+        mpsc::{Sender, Receiver, RecvTimeoutError},
+        atomic::{AtomicU64, Ordering},
     },
-    fs::File,
-    time::Duration,
-    path::Path,
+    time::{Duration, Instant, SystemTime},
+    path::{Path, PathBuf},
+    io::Write,
     thread::JoinHandle
 };
-use std::io::{
-    Seek,
-    BufRead
-};
-
-use notify::{Watcher, RecursiveMode};
 
 use inkbound_parser::parser::*;
 use atomic_enum::atomic_enum;
 
+use crate::broadcast::{EventBroadcast, EventReceiver, Overflow};
+use crate::source::{LogSource, SourceLine, Wake, WatcherBackend};
+use crate::replay::ReplayControl;
+use crate::checkpoint::Checkpoint;
+
 pub enum LogReaderCommand {
     Update,
     Stop,
 }
 
+/// What the reading thread is doing.
+/// Warnings about the log itself, e.g. a game version newer than any known patterns, don't change the status
+///  as reading carries on regardless, see `LogReader::get_warning`
 #[atomic_enum]
 #[derive(PartialEq)]
 pub enum LogReaderStatus {
@@ -32,6 +35,12 @@ pub enum LogReaderStatus {
     Reading,
     Idle,
     Errored,
+    /// The log was truncated or replaced (e.g. the game restarted) and has been reopened
+    Reopened,
+    /// The log doesn't exist yet, e.g. the game has never been run. Reading starts once it's created
+    WaitingForFile,
+    /// The source has no more lines, e.g. stdin was closed
+    Finished,
 }
 
 impl std::fmt::Display for LogReaderStatus {
@@ -41,46 +50,226 @@ impl std::fmt::Display for LogReaderStatus {
             LogReaderStatus::Reading => "Reading",
             LogReaderStatus::Idle => "Idle",
             LogReaderStatus::Errored => "Errored",
+            LogReaderStatus::Reopened => "Reopened",
+            LogReaderStatus::WaitingForFile => "Waiting for log file",
+            LogReaderStatus::Finished => "Finished",
         })
     }
 }
 
+/// Poll intervals the frontends offer, in milliseconds. Faster than this only costs CPU, slower and stats lag behind
+pub const POLL_INTERVAL_RANGE: std::ops::RangeInclusive<u64> = 10..=5000;
+
+#[derive(Debug, Clone)]
+pub struct LogReaderOptions {
+    poll_duration: Duration,
+    parser_options: LogParserOptions,
+    reset_on_reopen: bool,
+    checkpoint: Option<PathBuf>,
+    checkpoint_interval: Duration,
+    keep_dives: Option<usize>,
+    archive: Option<PathBuf>,
+}
+
+impl Default for LogReaderOptions {
+    fn default() -> Self {
+        Self {
+            poll_duration: Duration::from_secs(2),
+            parser_options: LogParserOptions::default(),
+            reset_on_reopen: false,
+            checkpoint: None,
+            checkpoint_interval: Duration::from_secs(30),
+            keep_dives: None,
+            archive: None,
+        }
+    }
+}
+
+impl LogReaderOptions {
+    /// How often to check the log for changes
+    pub fn poll_duration(self, poll_duration: Duration) -> Self {
+        Self {
+            poll_duration,
+            ..self
+        }
+    }
+
+    pub fn parser_options(self, parser_options: LogParserOptions) -> Self {
+        Self {
+            parser_options,
+            ..self
+        }
+    }
+
+    /// Clear the DataLog when the log is truncated or replaced, instead of starting a new session in it
+    pub fn reset_on_reopen(self, reset_on_reopen: bool) -> Self {
+        Self {
+            reset_on_reopen,
+            ..self
+        }
+    }
+
+    /// Save progress to a file periodically and when stopped, and resume from it when started again on the same log,
+    ///  instead of parsing the whole log again. Only sources that can resume are checkpointed, e.g. FileSource
+    pub fn checkpoint(self, checkpoint: Option<PathBuf>) -> Self {
+        Self {
+            checkpoint,
+            ..self
+        }
+    }
+
+    /// How often to save the checkpoint while the log is changing
+    pub fn checkpoint_interval(self, checkpoint_interval: Duration) -> Self {
+        Self {
+            checkpoint_interval,
+            ..self
+        }
+    }
+
+    /// Only keep full detail for this many of the most recent dives, older dives are compacted to their totals.
+    /// Keeps memory use and the cost of each snapshot flat over long sessions. See `DataLog::compact`
+    pub fn keep_dives(self, keep_dives: Option<usize>) -> Self {
+        Self {
+            keep_dives,
+            ..self
+        }
+    }
+
+    /// Append dives to this file in full before they're compacted, one json object per line
+    pub fn archive(self, archive: Option<PathBuf>) -> Self {
+        Self {
+            archive,
+            ..self
+        }
+    }
+}
+
+/// Counters for how reading is going, since reading started or was last reset
+#[derive(Debug, Clone, Default)]
+pub struct ReaderMetrics {
+    pub lines: u64,
+    pub bytes: u64,
+    /// Events parsed, by `Event::kind`
+    pub events: BTreeMap<&'static str, u64>,
+    /// Lines that looked like a known line, but couldn't be parsed
+    pub parse_errors: u64,
+    /// Errors reading from the source
+    pub read_errors: u64,
+    /// Bytes read and the size of the log, for sources that read from a file, see `lag`
+    pub offset: Option<u64>,
+    /// Checked whenever the metrics are fetched
+    pub size: Option<u64>,
+    /// When reading last caught up to `offset`. If this gets old while the log is growing, changes aren't being noticed
+    pub offset_checked: Option<SystemTime>,
+    pub last_event: Option<SystemTime>,
+    /// Time spent reading and parsing
+    pub busy: Duration,
+}
+
+impl ReaderMetrics {
+    /// How many bytes of the log are still to be read
+    pub fn lag(&self) -> Option<u64> {
+        Some(self.size?.saturating_sub(self.offset?))
+    }
+
+    /// Lines read per second of time spent reading
+    pub fn lines_per_second(&self) -> f64 {
+        match self.busy.is_zero() {
+            true => 0.,
+            false => self.lines as f64 / self.busy.as_secs_f64(),
+        }
+    }
+}
+
 pub struct LogReader {
     // Configuration items
-    filepath: String,
-    poll_duration: Duration,
+    description: String,
+    options: LogReaderOptions,
+    replay: Option<ReplayControl>,
     state: LogReaderState,
+    // Kept across resets, so consumers can keep comparing generations and don't need to resubscribe
+    changes: Arc<ChangeNotifier>,
+    events: Arc<EventBroadcast>,
+    // Handed back by the reading thread when it stops
+    stopped: Option<(Box<dyn LogSource>, LogParser)>,
+    /// The file being read, to check how far behind reading is
+    file: Option<PathBuf>,
+    watcher: Option<WatcherBackend>,
 }
 
-// All the non-configuration items that need to be (re)initialized
-struct LogReaderState {
+/// Tracks changes to the DataLog, and who to tell about them
+#[derive(Default)]
+struct ChangeNotifier {
+    generation: AtomicU64,
+    listeners: RwLock<Vec<Box<dyn Fn() + Send + Sync>>>,
+}
+
+impl ChangeNotifier {
+    fn notify(&self) {
+        self.generation.fetch_add(1, Ordering::Release);
+        for listener in self.listeners.read().unwrap().iter() {
+            listener();
+        }
+    }
+}
+
+// Handles shared between the LogReader and the reading thread
+#[derive(Clone)]
+pub(crate) struct SharedState {
     datalog: Arc<RwLock<DataLog>>,
-    sender: Sender<LogReaderCommand>,
+    /// Copy of the datalog as of the last change, so readers don't need to hold the lock or clone it themselves
+    snapshot: Arc<RwLock<Arc<DataLog>>>,
+    changes: Arc<ChangeNotifier>,
+    events: Arc<EventBroadcast>,
     status: Arc<AtomicLogReaderStatus>,
-    datalog_thread: Option<JoinHandle<()>>,
-    _watcher: Box<dyn Watcher>,
-}
-
-fn start_watcher(sender: Sender<LogReaderCommand>, status: Arc<AtomicLogReaderStatus>, filepath: &str, poll_duration: Duration) -> Box<dyn Watcher> {
-    let watcher_callback =
-        move |event| {
-            match event {
-                Ok(_events) => {
-                    log::trace!("file update received");
-                    if let Err(e) = sender.send(LogReaderCommand::Update) {
-                        log::error!("Error sending update to logging thread: {e:?}");
-                    }
-                },
-                Err(e) => {
-                    log::error!("Error from watcher: {:?}", e);
-                    status.store(LogReaderStatus::Errored, Ordering::Relaxed);
-                },
-            }
-        };
+    warning: Arc<RwLock<Option<String>>>,
+    error: Arc<RwLock<Option<String>>>,
+    diagnostics: Arc<RwLock<Option<ParserDiagnostics>>>,
+    metrics: Arc<RwLock<ReaderMetrics>>,
+}
 
-    let mut watcher = notify::PollWatcher::new(watcher_callback, notify::Config::default().with_poll_interval(poll_duration)).unwrap();
-    watcher.watch(Path::new(filepath), RecursiveMode::NonRecursive).unwrap();
-    Box::new(watcher)
+impl SharedState {
+    fn new(changes: Arc<ChangeNotifier>, events: Arc<EventBroadcast>) -> Self {
+        Self {
+            datalog: Arc::new(RwLock::new(DataLog::new())),
+            snapshot: Arc::new(RwLock::new(Arc::new(DataLog::new()))),
+            changes,
+            events,
+            status: Arc::new(AtomicLogReaderStatus::new(LogReaderStatus::Initializing)),
+            warning: Arc::new(RwLock::new(None)),
+            error: Arc::new(RwLock::new(None)),
+            diagnostics: Arc::new(RwLock::new(None)),
+            metrics: Arc::new(RwLock::new(ReaderMetrics::default())),
+        }
+    }
+
+    /// Publish a new snapshot of the datalog, and notify listeners
+    fn publish(&self) {
+        let snapshot = Arc::new(self.datalog.read().unwrap().clone());
+        *self.snapshot.write().unwrap() = snapshot;
+        self.changes.notify();
+    }
+
+    pub(crate) fn set_error(&self, message: String) {
+        log::error!("{message}");
+        *self.error.write().unwrap() = Some(message);
+        self.status.store(LogReaderStatus::Errored, Ordering::Relaxed);
+    }
+
+    fn clear_error(&self) {
+        let mut error = self.error.write().unwrap();
+        if error.is_some() {
+            *error = None;
+        }
+    }
+}
+
+// All the non-configuration items that need to be (re)initialized
+struct LogReaderState {
+    shared: SharedState,
+    sender: Sender<LogReaderCommand>,
+    /// Hands the source and parser back when stopped, so they can be restarted
+    datalog_thread: Option<JoinHandle<(Box<dyn LogSource>, LogParser)>>,
 }
 
 fn check_exit(rx: &Receiver<LogReaderCommand>) -> bool {
@@ -89,7 +278,7 @@ fn check_exit(rx: &Receiver<LogReaderCommand>) -> bool {
         Err(std::sync::mpsc::TryRecvError::Empty) => false,
 
         Ok(LogReaderCommand::Stop) => {
-            log::debug!("stop command received, exiting thread mid read");
+            log::debug!("stop command received, stopping after the current line");
             true
         },
         Err(e) => {
@@ -99,119 +288,402 @@ fn check_exit(rx: &Receiver<LogReaderCommand>) -> bool {
     }
 }
 
-fn init_datalog_thread(filepath: &str, status: Arc<AtomicLogReaderStatus>, sender: Sender<LogReaderCommand>, rx: Receiver<LogReaderCommand>, datalog: Arc<RwLock<DataLog>>, skip_current: bool) -> JoinHandle<()> {
-    let file = File::open(filepath).unwrap(); // TODO: unwrap
-    let mut reader = std::io::BufReader::new(file);
-
-    let mut parser = LogParser::new();
-    let datalog = datalog.clone();
+/// Save where reading is up to, if the source supports resuming.
+/// The snapshot is used for the datalog, so this should only be called once changes have been published
+fn save_checkpoint(path: &Path, source: &dyn LogSource, parser: &LogParser, shared: &SharedState) {
+    let Some(position) = source.position() else { return };
+    let datalog = shared.snapshot.read().unwrap().clone();
+    match Checkpoint::new(source.describe(), position, parser.state(), &datalog).save(path) {
+        Ok(()) => log::debug!("saved checkpoint to {}", path.display()),
+        Err(e) => log::warn!("unable to save checkpoint to {}: {e}", path.display()),
+    }
+}
 
-    if skip_current {
-        // Seek to end first before starting the thread
-        reader.seek(std::io::SeekFrom::End(0)).unwrap();
-    } else {
-        // Queue an update command so that the reading thread immediately starts
-        sender.send(LogReaderCommand::Update).unwrap();
+/// Append dives to the archive, with the session each was played in
+fn archive_dives(path: &Path, dives: &[(Option<Session>, DiveLog)]) -> std::io::Result<()> {
+    let mut file = std::io::BufWriter::new(std::fs::OpenOptions::new().create(true).append(true).open(path)?);
+    for (session, dive) in dives {
+        serde_json::to_writer(&mut file, &serde_json::json!({ "session": session, "dive": dive }))?;
+        file.write_all(b"\n")?;
     }
+    file.flush()
+}
 
+/// Start the thread that reads and parses the log.
+/// If the source can't reliably wake it, or while waiting for the log to be created, it also checks for updates on a timer.
+/// Stopping finishes the line being read, so everything read and broadcast so far is in the DataLog, published and checkpointed.
+fn init_datalog_thread(mut source: Box<dyn LogSource>, mut parser: LogParser, shared: SharedState, rx: Receiver<LogReaderCommand>, options: LogReaderOptions, watching: bool) -> JoinHandle<(Box<dyn LogSource>, LogParser)> {
     std::thread::spawn(move || {
-        let mut cache_string = String::new();
+        let mut last_checkpoint = Instant::now();
+        let mut unsaved = false;
         let mut cache_events = Vec::new();
-        loop {
-            match rx.recv() {
+        let mut waiting = false;
+        let mut finished = false;
+        // Continues from the previous thread's, if restarted without resetting
+        let mut metrics = shared.metrics.read().unwrap().clone();
+        // Parse errors already in metrics from the current parser, which is replaced when the log restarts
+        let mut parse_errors = parser.partial_matches();
+        let mut stopping = false;
+        while !stopping {
+            let command = if finished || (watching && !waiting) {
+                rx.recv().map_err(|e| format!("{e:?}"))
+            } else {
+                match rx.recv_timeout(options.poll_duration) {
+                    Err(RecvTimeoutError::Timeout) => Ok(LogReaderCommand::Update),
+                    command => command.map_err(|e| format!("{e:?}")),
+                }
+            };
+
+            match command {
                 Ok(LogReaderCommand::Update) => {
-                    while reader.read_line(&mut cache_string).is_ok_and(|size| size != 0) {
-                        if check_exit(&rx) {
-                            return
-                        }
-                        // TODO: probably use fetch-update
-                        if status.load(Ordering::Relaxed) != LogReaderStatus::Initializing {
-                            status.store(LogReaderStatus::Reading, Ordering::Relaxed);
+                    let started = Instant::now();
+                    let mut reopened = false;
+                    let mut changed = false;
+                    let mut errored = false;
+                    waiting = false;
+                    finished = false;
+                    loop {
+                        let mut restart = None;
+                        loop {
+                            let line = match source.next_line() {
+                                Ok(SourceLine::Line(line)) => line,
+                                Ok(SourceLine::Pending) => break,
+                                Ok(line @ (SourceLine::Restarted | SourceLine::Rewound)) => {
+                                    restart = Some(line);
+                                    break
+                                },
+                                Ok(SourceLine::Waiting) => {
+                                    if shared.status.load(Ordering::Relaxed) != LogReaderStatus::WaitingForFile {
+                                        log::info!("waiting for {} to be created", source.describe());
+                                    }
+                                    waiting = true;
+                                    break
+                                },
+                                Ok(SourceLine::End) => {
+                                    log::info!("finished reading {}", source.describe());
+                                    finished = true;
+                                    break
+                                },
+                                Err(e) => {
+                                    shared.set_error(format!("Error reading {}: {e}", source.describe()));
+                                    metrics.read_errors += 1;
+                                    errored = true;
+                                    break
+                                },
+                            };
+                            // TODO: probably use fetch-update
+                            if shared.status.load(Ordering::Relaxed) != LogReaderStatus::Initializing {
+                                shared.status.store(LogReaderStatus::Reading, Ordering::Relaxed);
+                            }
+                            metrics.lines += 1;
+                            metrics.bytes += source.line_bytes().unwrap_or(line.len() as u64);
+                            if let Some(event) = parser.parse_line(&line) {
+                                *metrics.events.entry(event.kind()).or_default() += 1;
+                                metrics.last_event = Some(SystemTime::now());
+                                if let Event::GameVersion(_, info) = &event {
+                                    *shared.warning.write().unwrap() = info.newer_than_known.then(||
+                                        format!("Game version {} is newer than any known log format, stats may be inaccurate", info.version)
+                                    );
+                                }
+                                shared.events.send(&event);
+                                cache_events.push(event);
+                            }
+                            if check_exit(&rx) {
+                                stopping = true;
+                                break
+                            }
                         }
-                        if let Some(event) = parser.parse_line(cache_string.as_str()) {
-                            cache_events.push(event);
+
+                        reopened |= restart == Some(SourceLine::Restarted);
+                        changed |= restart.is_some() || !cache_events.is_empty();
+                        // Events collected, now acquire write lock to update the datalog
+                        {
+                            let mut datalog = shared.datalog.write().unwrap();
+                            for event in cache_events.drain(..) {
+                                datalog.handle_event(event);
+                            }
+
+                            let Some(restart) = restart else {
+                                break
+                            };
+                            // The game recreates the log when it restarts, so what follows is a new log
+                            metrics.parse_errors += (parser.partial_matches() - parse_errors) as u64;
+                            parse_errors = 0;
+                            parser = LogParser::with_options(options.parser_options.clone());
+                            if restart == SourceLine::Rewound || options.reset_on_reopen {
+                                *datalog = DataLog::new();
+                            } else {
+                                datalog.new_session();
+                            }
                         }
-                        cache_string.clear();
                     }
-
-                    // Events collected, now acquire write lock to update the datalog
-                    {
-                        let mut datalog = datalog.write().unwrap();
-                        for event in cache_events.drain(..) {
-                            if check_exit(&rx) {
-                                return;
+                    if let (Some(keep), true) = (options.keep_dives, changed) {
+                        let compacted: Vec<(Option<Session>, DiveLog)> = {
+                            let mut datalog = shared.datalog.write().unwrap();
+                            let dives = datalog.compact(keep);
+                            dives.into_iter().map(|dive| (datalog.session_of(&dive).cloned(), dive)).collect()
+                        };
+                        if let (Some(path), false) = (options.archive.as_deref(), compacted.is_empty()) {
+                            if let Err(e) = archive_dives(path, &compacted) {
+                                log::warn!("unable to archive dives to {}: {e}", path.display());
                             }
-                            datalog.handle_event(event);
                         }
                     }
+                    if changed {
+                        shared.publish();
+                        unsaved = true;
+                    }
+                    if let Some(path) = options.checkpoint.as_deref() {
+                        if unsaved && last_checkpoint.elapsed() >= options.checkpoint_interval {
+                            save_checkpoint(path, source.as_ref(), &parser, &shared);
+                            last_checkpoint = Instant::now();
+                            unsaved = false;
+                        }
+                    }
+
+                    if let Some(parser_diagnostics) = parser.diagnostics() {
+                        *shared.diagnostics.write().unwrap() = Some(parser_diagnostics.clone());
+                    }
 
-                    status.store(LogReaderStatus::Idle, Ordering::Relaxed);
+                    metrics.parse_errors += (parser.partial_matches() - parse_errors) as u64;
+                    parse_errors = parser.partial_matches();
+                    metrics.offset = source.offset();
+                    metrics.offset_checked = metrics.offset.map(|_| SystemTime::now());
+                    metrics.busy += started.elapsed();
+                    *shared.metrics.write().unwrap() = metrics.clone();
+
+                    if stopping {
+                        // Stopped partway through, not caught up, so leave the status for a restarted thread to update
+                    } else if errored {
+                        // Status and error already set, keep them up until the next successful update
+                    } else if waiting {
+                        shared.clear_error();
+                        shared.status.store(LogReaderStatus::WaitingForFile, Ordering::Relaxed);
+                    } else if finished {
+                        shared.clear_error();
+                        shared.status.store(LogReaderStatus::Finished, Ordering::Relaxed);
+                    } else {
+                        shared.clear_error();
+                        // Leave Reopened up until the next update, so it's visible for more than an instant
+                        shared.status.store(if reopened { LogReaderStatus::Reopened } else { LogReaderStatus::Idle }, Ordering::Relaxed);
+                    }
                 },
                 Ok(LogReaderCommand::Stop) => {
                     log::debug!("stop command received, closing logging thread");
-                    return
+                    stopping = true;
                 },
                 Err(e) => {
-                    log::error!("Error receiving from channel inside logging thread {e:?}");
-                    return
+                    log::error!("Error receiving from channel inside logging thread {e}");
+                    stopping = true;
                 },
             }
         }
+
+        if let (Some(path), true) = (options.checkpoint.as_deref(), unsaved) {
+            save_checkpoint(path, source.as_ref(), &parser, &shared);
+        }
+        (source, parser)
     })
 }
 
 impl LogReaderState {
-    fn new(filepath: &str, poll_duration: Duration, skip_current: bool) -> Self {
-        let (sender, rx) = std::sync::mpsc::channel();
+    /// Start reading from the source, continuing from the checkpoint if `resume` and there is one for this source
+    fn new(mut source: Box<dyn LogSource>, options: LogReaderOptions, changes: Arc<ChangeNotifier>, events: Arc<EventBroadcast>, resume: bool) -> Self {
+        let shared = SharedState::new(changes, events);
+        let checkpoint = options.checkpoint.as_deref()
+            .filter(|_| resume)
+            .and_then(Checkpoint::load)
+            .filter(|checkpoint| checkpoint.source == source.describe() && source.resume(&checkpoint.position));
+        let parser = match checkpoint {
+            Some(checkpoint) => {
+                log::info!("resuming {} from offset {}", checkpoint.source, checkpoint.position.offset);
+                *shared.datalog.write().unwrap() = checkpoint.datalog.into_owned();
+                shared.publish();
+                LogParser::with_state(options.parser_options.clone(), checkpoint.parser)
+            },
+            None => LogParser::with_options(options.parser_options.clone()),
+        };
+        Self::start(source, parser, shared, options)
+    }
 
-        let datalog = Arc::new(RwLock::new(DataLog::new()));
-        let status = Arc::new(AtomicLogReaderStatus::new(LogReaderStatus::Initializing));
-        let datalog_thread = Some(init_datalog_thread(filepath, status.clone(), sender.clone(), rx, datalog.clone(), skip_current));
-        let _watcher = start_watcher(sender.clone(), status.clone(), filepath, poll_duration);
+    /// Start the source and reading thread, adding to whatever is already in the shared state
+    fn start(mut source: Box<dyn LogSource>, parser: LogParser, shared: SharedState, options: LogReaderOptions) -> Self {
+        let (sender, rx) = std::sync::mpsc::channel();
+        let watching = source.start(Wake { sender: sender.clone(), shared: shared.clone() }, options.poll_duration);
+        // Read whatever is already there
+        sender.send(LogReaderCommand::Update).unwrap();
+        let datalog_thread = Some(init_datalog_thread(source, parser, shared.clone(), rx, options, watching));
 
         Self {
+            shared,
             sender,
-            datalog,
-            status,
             datalog_thread,
-            _watcher,
         }
     }
 }
 
 impl LogReader {
-    pub fn new(filepath: String, poll_duration: Duration, skip_current: bool) -> Self {
-        let state = LogReaderState::new(&filepath, poll_duration, skip_current);
+    pub fn new(source: impl LogSource + 'static, options: LogReaderOptions) -> Self {
+        let changes = Arc::new(ChangeNotifier::default());
+        let events = Arc::new(EventBroadcast::default());
+        let description = source.describe();
+        let replay = source.replay();
+        let file = source.file();
+        let watcher = source.watcher();
+        let state = LogReaderState::new(Box::new(source), options.clone(), changes.clone(), events.clone(), true);
 
         Self {
             state,
-            filepath,
-            poll_duration,
+            description,
+            options,
+            replay,
+            changes,
+            events,
+            stopped: None,
+            file,
+            watcher,
         }
     }
 
+    /// Get the live datalog, which the reading thread writes to.
+    /// Prefer `snapshot` for reading, which doesn't block the reading thread
     pub fn get_datalog(&self) -> Arc<RwLock<DataLog>> {
-        self.state.datalog.clone()
+        self.state.shared.datalog.clone()
     }
 
-    /// Reset the logreader. Clear the datalog, restart the watcher and logging thread.
+    /// Get the datalog as of the last change. Cheap, the snapshot is only copied once per change
+    pub fn snapshot(&self) -> Arc<DataLog> {
+        self.state.shared.snapshot.read().unwrap().clone()
+    }
+
+    /// Counter that increases every time the datalog changes, including on reset.
+    /// Compare with a previously seen value to know whether anything derived from the datalog needs rebuilding
+    pub fn generation(&self) -> u64 {
+        self.changes.generation.load(Ordering::Acquire)
+    }
+
+    /// Call a function every time the datalog changes, e.g. to request a repaint.
+    /// Called from the reading thread, so it should return quickly
+    pub fn on_change(&self, listener: impl Fn() + Send + Sync + 'static) {
+        self.changes.listeners.write().unwrap().push(Box::new(listener));
+    }
+
+    /// Reset the logreader. Clear the datalog, restart the source and logging thread.
     pub fn reset(&mut self) {
         self.cleanup();
 
-        let state = LogReaderState::new(&self.filepath, self.poll_duration, false);
+        let Some((mut source, _)) = self.stopped.take() else {
+            log::error!("unable to reset, the reading thread for {} did not return its source", self.description);
+            return
+        };
+        source.reset();
+        let state = LogReaderState::new(source, self.options.clone(), self.changes.clone(), self.events.clone(), false);
 
         self.state = state;
+        self.changes.notify();
+    }
+
+    pub fn poll_duration(&self) -> Duration {
+        self.options.poll_duration
+    }
+
+    /// Change how often the log is checked for changes. Restarts the source, but keeps everything read so far
+    pub fn set_poll_duration(&mut self, poll_duration: Duration) {
+        if poll_duration == self.options.poll_duration {
+            return
+        }
+        self.options = self.options.clone().poll_duration(poll_duration);
+        self.restart(|_| ());
+    }
+
+    /// How changes to the log are noticed, if the source watches a file
+    pub fn watcher(&self) -> Option<WatcherBackend> {
+        self.watcher
+    }
+
+    /// Change how changes to the log are noticed. Restarts the source, but keeps everything read so far
+    pub fn set_watcher(&mut self, backend: WatcherBackend) {
+        if self.watcher.is_none_or(|watcher| watcher == backend) {
+            return
+        }
+        self.watcher = Some(backend);
+        self.restart(|source| source.set_watcher(backend));
+    }
+
+    /// Stop reading and start again with a changed source or options, continuing from where reading was up to
+    fn restart(&mut self, change: impl FnOnce(&mut dyn LogSource)) {
+        self.cleanup();
+
+        let Some((mut source, parser)) = self.stopped.take() else {
+            log::error!("unable to restart, the reading thread for {} did not return its source", self.description);
+            return
+        };
+        change(source.as_mut());
+        self.state = LogReaderState::start(source, parser, self.state.shared.clone(), self.options.clone());
     }
 
     pub fn get_status(&self) -> LogReaderStatus {
-        self.state.status.load(Ordering::Relaxed)
+        self.state.shared.status.load(Ordering::Relaxed)
+    }
+
+    /// Receive every event parsed from now on, e.g. for recording or exporting.
+    /// Up to `capacity` events are queued, after which `overflow` decides whether to drop events or wait for the receiver.
+    /// NOTE: events from before subscribing are not replayed, subscribe before reading starts to see them all
+    pub fn subscribe(&self, capacity: usize, overflow: Overflow) -> EventReceiver {
+        self.events.subscribe(capacity, overflow)
+    }
+
+    /// Call a function with every event parsed from now on.
+    /// Called from the reading thread before the event reaches the DataLog, so it should return quickly
+    pub fn on_event(&self, callback: impl Fn(&Event) + Send + Sync + 'static) {
+        self.events.on_event(callback);
+    }
+
+    /// Where the log is being read from, e.g. its path
+    pub fn source_description(&self) -> &str {
+        &self.description
+    }
+
+    /// Controls for pausing and seeking, when reading a replay
+    pub fn replay(&self) -> Option<&ReplayControl> {
+        self.replay.as_ref()
+    }
+
+    /// Get a description of the last error, while the status is Errored
+    pub fn get_error(&self) -> Option<String> {
+        self.state.shared.error.read().unwrap().clone()
+    }
+
+    /// Get any warning about the log being read, e.g. an unsupported game version.
+    /// Kept apart from `get_status`, as the warning stays for as long as the log is read.
+    /// NOTE: the bundled patterns don't detect the game version yet, so this only warns with a pattern file that does
+    pub fn get_warning(&self) -> Option<String> {
+        self.state.shared.warning.read().unwrap().clone()
+    }
+
+    /// Get the parser's coverage diagnostics, if enabled in the parser options
+    pub fn get_diagnostics(&self) -> Option<ParserDiagnostics> {
+        self.state.shared.diagnostics.read().unwrap().clone()
+    }
+
+    /// Get counters for lines, events and errors, and how far behind the end of the log reading is
+    pub fn metrics(&self) -> ReaderMetrics {
+        let mut metrics = self.state.shared.metrics.read().unwrap().clone();
+        // Checked here rather than by the reading thread, which may have stopped noticing the log change
+        metrics.size = self.file.as_ref()
+            .and_then(|file| std::fs::metadata(file).ok())
+            .map(|metadata| metadata.len());
+        metrics
     }
 
     fn cleanup(&mut self) {
         self.state.sender.send(LogReaderCommand::Stop).ok(); // Thread may already have exited, ignore send errors here
         if let Some(thread) = self.state.datalog_thread.take() {
             log::debug!("waiting on parser thread to close...");
-            thread.join().unwrap();
+            match thread.join() {
+                Ok(stopped) => self.stopped = Some(stopped),
+                Err(_) => log::error!("parser thread panicked"),
+            }
             log::debug!("parser thread closed!")
         }
     }
@@ -222,3 +694,284 @@ impl Drop for LogReader {
         self.cleanup();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+    use crate::{FileSource, MemorySource, StreamSource, WatcherBackend};
+
+    const DIVE_START: &str = "0T23:24:45 80 I Party run start triggered - solo party: False\n";
+
+    /// Poll until a condition is true, or give up after a few seconds
+    fn wait_for(mut condition: impl FnMut() -> bool) -> bool {
+        for _ in 0..100 {
+            if condition() {
+                return true
+            }
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        false
+    }
+
+    #[test]
+    fn wait_for_file() {
+        let dir = std::env::temp_dir().join(format!("logreader_wait_{}", std::process::id()));
+        let path = dir.join("logfile.log");
+        let options = LogReaderOptions::default().poll_duration(Duration::from_millis(20));
+
+        // Directory doesn't exist either, so no watcher
+        let reader = LogReader::new(FileSource::new(path.to_string_lossy()), options);
+        let notified = Arc::new(AtomicU64::new(0));
+        let counter = notified.clone();
+        reader.on_change(move || { counter.fetch_add(1, Ordering::Relaxed); });
+        assert!(wait_for(|| reader.get_status() == LogReaderStatus::WaitingForFile));
+        assert_eq!(reader.get_error(), None);
+        assert_eq!(reader.generation(), 0);
+
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(&path, DIVE_START).unwrap();
+        assert!(wait_for(|| reader.snapshot().dives.len() == 1));
+        assert!(wait_for(|| reader.get_status() == LogReaderStatus::Idle));
+        assert_eq!(reader.generation(), 1);
+        assert_eq!(notified.load(Ordering::Relaxed), 1);
+
+        drop(reader);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn relaunch_same_character() {
+        let dir = std::env::temp_dir().join(format!("logreader_relaunch_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("logfile.log");
+        let log = format!("0T00:44:47 45 I Joining hub - characterId: 00000000000, characterName: Main, partyId: 0\n{DIVE_START}");
+        std::fs::write(&path, &log).unwrap();
+        let reader = LogReader::new(FileSource::new(path.to_string_lossy()), LogReaderOptions::default().poll_duration(Duration::from_millis(20)));
+        assert!(wait_for(|| reader.snapshot().dives.len() == 1));
+
+        // The game writes a new log on every launch, which is the only sign of a relaunch with the same character
+        std::fs::write(dir.join("new.log"), &log).unwrap();
+        std::fs::rename(dir.join("new.log"), &path).unwrap();
+        assert!(wait_for(|| reader.snapshot().dives.len() == 2));
+        let characters: Vec<Option<String>> = reader.snapshot().sessions.iter().map(|s| s.character.clone()).collect();
+        assert_eq!(characters, vec![Some("Main".to_string()), Some("Main".to_string())]);
+
+        drop(reader);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn resume_from_checkpoint() {
+        let dir = std::env::temp_dir().join(format!("logreader_checkpoint_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("logfile.log").to_string_lossy().to_string();
+        let options = LogReaderOptions::default().checkpoint(Some(dir.join("checkpoint.json")));
+        std::fs::write(&path, format!("0T23:00:00 80 I first launch\n{DIVE_START}")).unwrap();
+
+        let reader = LogReader::new(FileSource::new(&path), options.clone());
+        assert!(wait_for(|| reader.snapshot().dives.len() == 1));
+        drop(reader);
+
+        // Blank out the dive that was already read, so it only counts if it comes from the checkpoint
+        let blank = format!("{}\n", " ".repeat(DIVE_START.len() - 1));
+        std::fs::write(&path, format!("0T23:00:00 80 I first launch\n{blank}{DIVE_START}")).unwrap();
+        let reader = LogReader::new(FileSource::new(&path), options.clone());
+        assert!(wait_for(|| reader.get_status() == LogReaderStatus::Idle));
+        assert_eq!(reader.snapshot().dives.len(), 2);
+        drop(reader);
+
+        // Skipping the current log doesn't bring back what was read from it
+        let reader = LogReader::new(FileSource::new(&path).skip_current(true), options.clone());
+        assert!(wait_for(|| reader.get_status() == LogReaderStatus::Idle));
+        assert_eq!(reader.snapshot().dives.len(), 0);
+        drop(reader);
+
+        // A new log with the same first line starts over
+        let new = dir.join("new.log");
+        std::fs::write(&new, format!("0T23:00:00 80 I first launch\n{blank}{DIVE_START}")).unwrap();
+        std::fs::rename(&new, &path).unwrap();
+        let reader = LogReader::new(FileSource::new(&path), options.clone());
+        assert!(wait_for(|| reader.get_status() == LogReaderStatus::Idle));
+        assert_eq!(reader.snapshot().dives.len(), 1);
+        drop(reader);
+
+        // A different log starts over
+        std::fs::write(&path, format!("0T09:00:00 80 I second launch\n{DIVE_START}")).unwrap();
+        let reader = LogReader::new(FileSource::new(&path), options);
+        assert!(wait_for(|| reader.get_status() == LogReaderStatus::Idle));
+        assert_eq!(reader.snapshot().dives.len(), 1);
+
+        drop(reader);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// Write a generated log, and read it in one go for comparison
+    fn large_log(path: &Path) -> serde_json::Value {
+        let log = inkbound_parser::generator::LogGenerator::new(inkbound_parser::generator::GeneratorOptions::default().dives(5)).generate();
+        std::fs::write(path, log).unwrap();
+        let reader = LogReader::new(FileSource::new(path.to_string_lossy()), LogReaderOptions::default());
+        assert!(wait_for(|| reader.get_status() == LogReaderStatus::Idle));
+        serde_json::to_value(&*reader.snapshot()).unwrap()
+    }
+
+    /// Slow down reading, so the test can act while it's still going. Returns the number of events read so far
+    fn slow_down(reader: &LogReader) -> Arc<AtomicUsize> {
+        let events = Arc::new(AtomicUsize::new(0));
+        let counter = events.clone();
+        reader.on_event(move |_| {
+            counter.fetch_add(1, Ordering::Relaxed);
+            std::thread::sleep(Duration::from_micros(200));
+        });
+        assert!(wait_for(|| events.load(Ordering::Relaxed) > 0));
+        events
+    }
+
+    #[test]
+    fn restart_mid_read() {
+        let path = std::env::temp_dir().join(format!("logreader_restart_{}.log", std::process::id()));
+        let expected = large_log(&path);
+
+        let mut reader = LogReader::new(FileSource::new(path.to_string_lossy()), LogReaderOptions::default());
+        let events = slow_down(&reader);
+        reader.set_poll_duration(Duration::from_millis(20));
+        let restarted = events.load(Ordering::Relaxed);
+        reader.set_watcher(WatcherBackend::Poll);
+        assert!(wait_for(|| reader.get_status() == LogReaderStatus::Idle));
+        assert!(restarted < events.load(Ordering::Relaxed), "finished reading before restarting");
+        assert_eq!(serde_json::to_value(&*reader.snapshot()).unwrap(), expected);
+
+        drop(reader);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn checkpoint_on_stop() {
+        let dir = std::env::temp_dir().join(format!("logreader_checkpoint_stop_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("logfile.log");
+        let expected = large_log(&path);
+        let checkpoint = dir.join("checkpoint.json");
+        let options = LogReaderOptions::default().checkpoint(Some(checkpoint.clone()));
+
+        // Stopped partway through, long before the checkpoint interval
+        let reader = LogReader::new(FileSource::new(path.to_string_lossy()), options.clone());
+        slow_down(&reader);
+        drop(reader);
+        let saved = Checkpoint::load(&checkpoint).expect("checkpoint saved on stop");
+        assert!(saved.position.offset < std::fs::metadata(&path).unwrap().len(), "finished reading before stopping");
+
+        // Continuing from it gives the same result as reading in one go
+        let reader = LogReader::new(FileSource::new(path.to_string_lossy()), options);
+        assert!(wait_for(|| reader.get_status() == LogReaderStatus::Idle));
+        assert_eq!(serde_json::to_value(&*reader.snapshot()).unwrap(), expected);
+
+        drop(reader);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn memory_source() {
+        let mut reader = LogReader::new(MemorySource::new(&DIVE_START.repeat(2)), LogReaderOptions::default());
+        assert!(wait_for(|| reader.get_status() == LogReaderStatus::Finished));
+        assert_eq!(reader.snapshot().dives.len(), 2);
+
+        // Reading starts over from the beginning after a reset
+        reader.reset();
+        assert!(wait_for(|| reader.get_status() == LogReaderStatus::Finished));
+        assert_eq!(reader.snapshot().dives.len(), 2);
+    }
+
+    #[test]
+    fn native_watcher() {
+        let path = std::env::temp_dir().join(format!("logreader_native_{}.log", std::process::id()));
+        std::fs::write(&path, DIVE_START).unwrap();
+        // Polling this slowly, only the native watcher would notice in time
+        let options = LogReaderOptions::default().poll_duration(Duration::from_secs(60));
+        let mut reader = LogReader::new(FileSource::new(path.to_string_lossy()).watcher(WatcherBackend::Native), options);
+        assert!(wait_for(|| reader.snapshot().dives.len() == 1));
+
+        std::fs::OpenOptions::new().append(true).open(&path).unwrap().write_all(DIVE_START.as_bytes()).unwrap();
+        assert!(wait_for(|| reader.snapshot().dives.len() == 2));
+
+        // Restarting with a different poll duration keeps what was read, and carries on from there
+        reader.set_poll_duration(Duration::from_millis(20));
+        assert_eq!(reader.poll_duration(), Duration::from_millis(20));
+        std::fs::OpenOptions::new().append(true).open(&path).unwrap().write_all(DIVE_START.as_bytes()).unwrap();
+        assert!(wait_for(|| reader.snapshot().dives.len() == 3));
+        assert_eq!(reader.metrics().lines, 3);
+
+        // Likewise for switching to polling
+        reader.set_watcher(WatcherBackend::Poll);
+        assert_eq!(reader.watcher(), Some(WatcherBackend::Poll));
+        std::fs::OpenOptions::new().append(true).open(&path).unwrap().write_all(DIVE_START.as_bytes()).unwrap();
+        assert!(wait_for(|| reader.snapshot().dives.len() == 4));
+        assert_eq!(reader.metrics().lines, 4);
+
+        drop(reader);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn metrics() {
+        let log = format!("{DIVE_START}unrelated line\n0T23:25:00 80 I Party run start triggered - solo party: False\n");
+        let reader = LogReader::new(MemorySource::new(&log), LogReaderOptions::default());
+        assert!(wait_for(|| reader.get_status() == LogReaderStatus::Finished));
+
+        let metrics = reader.metrics();
+        assert_eq!(metrics.lines, 3);
+        assert_eq!(metrics.bytes, log.len() as u64);
+        assert_eq!(metrics.events.get("StartDive"), Some(&2));
+        assert_eq!(metrics.read_errors, 0);
+        assert!(metrics.last_event.is_some());
+        // Memory has no file to fall behind on
+        assert_eq!(metrics.lag(), None);
+    }
+
+    #[test]
+    fn file_metrics() {
+        let path = std::env::temp_dir().join(format!("logreader_metrics_{}.log", std::process::id()));
+        let log = [DIVE_START.as_bytes(), b"invalid \xff\xfe utf8\n"].concat();
+        std::fs::write(&path, &log).unwrap();
+        // Polling this slowly, nothing appended is read during the test
+        let options = LogReaderOptions::default().poll_duration(Duration::from_secs(60));
+        let reader = LogReader::new(FileSource::new(path.to_string_lossy()).watcher(WatcherBackend::Poll), options);
+        assert!(wait_for(|| reader.get_status() == LogReaderStatus::Idle));
+
+        let metrics = reader.metrics();
+        assert_eq!(metrics.bytes, log.len() as u64);
+        assert_eq!(metrics.lag(), Some(0));
+        assert!(metrics.offset_checked.is_some());
+
+        // Falling behind shows without the reading thread noticing
+        std::fs::OpenOptions::new().append(true).open(&path).unwrap().write_all(DIVE_START.as_bytes()).unwrap();
+        assert_eq!(reader.metrics().lag(), Some(DIVE_START.len() as u64));
+
+        drop(reader);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn keep_dives() {
+        let archive = std::env::temp_dir().join(format!("logreader_archive_{}.jsonl", std::process::id()));
+        let options = LogReaderOptions::default().keep_dives(Some(1)).archive(Some(archive.clone()));
+        let reader = LogReader::new(MemorySource::new(&DIVE_START.repeat(3)), options);
+        assert!(wait_for(|| reader.get_status() == LogReaderStatus::Finished));
+
+        let compacted: Vec<bool> = reader.snapshot().dives.iter().map(|dive| dive.compacted).collect();
+        assert_eq!(compacted, vec![false, true, true]);
+        assert_eq!(std::fs::read_to_string(&archive).unwrap().lines().count(), 2);
+
+        drop(reader);
+        std::fs::remove_file(&archive).unwrap();
+    }
+
+    #[test]
+    fn stream_source() {
+        let stream = std::io::Cursor::new(DIVE_START.as_bytes().to_vec());
+        let reader = LogReader::new(StreamSource::new(stream, "test"), LogReaderOptions::default());
+        assert_eq!(reader.source_description(), "test");
+        assert!(wait_for(|| reader.get_status() == LogReaderStatus::Finished));
+        assert_eq!(reader.snapshot().dives.len(), 1);
+    }
+}
@@ -0,0 +1,421 @@
+//! Streaming a log from one machine to another, e.g. to show the overlay on a second computer.
+//!
+//! The protocol is line based. On connecting the receiver sends `RESUME <session> <offset>`, where `-` is used
+//!  for the session on the first connection. The forwarder answers `SESSION <session> <offset>` with where it's
+//!  starting from, then sends `LINE <end offset> <line>` for every line in the log, and `PING` while idle.
+//! Offsets are always the start of a line, a receiver resuming anywhere else starts over.
+//! A session identifies one log file, so a game restart shows up as a new session starting at offset 0.
+use std::{
+    io::{self, BufRead, BufReader, BufWriter, ErrorKind, Write},
+    net::{TcpListener, TcpStream, Shutdown},
+    sync::{
+        Arc,
+        Mutex,
+        atomic::{AtomicBool, Ordering},
+        mpsc::{Receiver, Sender},
+    },
+    time::{Duration, Instant},
+};
+
+use crate::source::{LogSource, OpenLog, SharedWake, SourceLine, Wake};
+
+/// How often the forwarder lets an idle receiver know it's still there
+const HEARTBEAT: Duration = Duration::from_secs(2);
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, format!("unexpected message: {}", message.trim_end()))
+}
+
+/// Parse `<session> <offset>`, the arguments of both RESUME and SESSION
+fn parse_position(args: &str) -> Option<(Option<String>, u64)> {
+    let (session, offset) = args.trim_end().split_once(' ')?;
+    let session = (session != "-").then(|| session.to_string());
+    Some((session, offset.parse().ok()?))
+}
+
+/// Serves a log file to any number of `RemoteSource`s, following it as the game writes to it
+#[derive(Debug, Clone)]
+pub struct Forwarder {
+    filepath: String,
+    poll_duration: Duration,
+}
+
+impl Forwarder {
+    pub fn new(filepath: impl Into<String>) -> Self {
+        Self {
+            filepath: filepath.into(),
+            poll_duration: Duration::from_millis(250),
+        }
+    }
+
+    /// How often to check the log for new lines
+    pub fn poll_duration(self, poll_duration: Duration) -> Self {
+        Self {
+            poll_duration,
+            ..self
+        }
+    }
+
+    /// Accept connections forever, serving each on its own thread
+    pub fn serve(&self, listener: TcpListener) -> io::Result<()> {
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    log::warn!("error accepting connection: {e}");
+                    continue
+                },
+            };
+            let peer = stream.peer_addr().map_or_else(|_| "unknown".to_string(), |peer| peer.to_string());
+            log::info!("{peer} connected");
+            let forwarder = self.clone();
+            std::thread::spawn(move || {
+                match forwarder.serve_client(stream) {
+                    Ok(()) => log::info!("{peer} disconnected"),
+                    Err(e) => log::info!("{peer} disconnected: {e}"),
+                }
+            });
+        }
+        Ok(())
+    }
+
+    fn serve_client(&self, stream: TcpStream) -> io::Result<()> {
+        let mut request = String::new();
+        BufReader::new(stream.try_clone()?).read_line(&mut request)?;
+        let mut resume = request.strip_prefix("RESUME ")
+            .and_then(parse_position)
+            .ok_or_else(|| invalid(&request))?;
+        let mut writer = BufWriter::new(stream);
+
+        let mut log: Option<OpenLog> = None;
+        let mut last_write = Instant::now();
+        loop {
+            let Some(current) = log.as_mut() else {
+                match OpenLog::open(&self.filepath) {
+                    Ok(mut opened) => {
+                        let session = opened.identity.id();
+                        let length = opened.reader.get_ref().metadata()?.len();
+                        // Only resume at the start of a line in the same log, anything else is new to the receiver
+                        let start = match std::mem::take(&mut resume) {
+                            (Some(resumed), offset) if resumed == session && offset <= length && opened.line_start(offset)? => offset,
+                            _ => 0,
+                        };
+                        opened.seek(io::SeekFrom::Start(start))?;
+                        writeln!(writer, "SESSION {session} {start}")?;
+                        log = Some(opened);
+                    },
+                    Err(e) if e.kind() == ErrorKind::NotFound => std::thread::sleep(self.poll_duration),
+                    Err(e) => return Err(e),
+                }
+                continue
+            };
+
+            match current.next_line()? {
+                Some(line) => {
+                    write!(writer, "LINE {} {line}", current.offset)?;
+                    last_write = Instant::now();
+                },
+                None => {
+                    if last_write.elapsed() >= HEARTBEAT {
+                        writeln!(writer, "PING")?;
+                        last_write = Instant::now();
+                    }
+                    writer.flush()?;
+                    match current.changed(&self.filepath) {
+                        Ok(None) => std::thread::sleep(self.poll_duration),
+                        Ok(Some(reason)) => {
+                            log::info!("log file was {reason}, reopening");
+                            log = None;
+                        },
+                        Err(e) if e.kind() == ErrorKind::NotFound => log = None,
+                        Err(e) => return Err(e),
+                    }
+                },
+            }
+        }
+    }
+}
+
+/// Receives a log from a `Forwarder` on another machine.
+/// Reconnects whenever the connection is lost, resuming after the last line received
+pub struct RemoteSource {
+    address: String,
+    lines: Option<Receiver<SourceLine>>,
+    wake: SharedWake,
+    /// Tells the background thread to stop, replaced when it is restarted
+    stop: Arc<AtomicBool>,
+    /// Current connection, to shut down when stopping
+    socket: Arc<Mutex<Option<TcpStream>>>,
+}
+
+impl RemoteSource {
+    pub fn new(address: impl Into<String>) -> Self {
+        Self {
+            address: address.into(),
+            lines: None,
+            wake: SharedWake::default(),
+            stop: Arc::new(AtomicBool::new(false)),
+            socket: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    fn stop_thread(&mut self) {
+        self.stop.store(true, Ordering::Release);
+        if let Some(socket) = self.socket.lock().unwrap().take() {
+            socket.shutdown(Shutdown::Both).ok();
+        }
+        self.lines = None;
+    }
+}
+
+/// Where the receiver is up to, kept across reconnections
+#[derive(Default)]
+struct Progress {
+    session: Option<String>,
+    offset: u64,
+}
+
+struct Connection {
+    address: String,
+    sender: Sender<SourceLine>,
+    wake: SharedWake,
+    stop: Arc<AtomicBool>,
+    socket: Arc<Mutex<Option<TcpStream>>>,
+}
+
+impl Connection {
+    fn run(self, retry: Duration) {
+        let mut progress = Progress::default();
+        while !self.stop.load(Ordering::Acquire) {
+            let e = match self.receive(&mut progress) {
+                Ok(()) => return,
+                Err(e) => e,
+            };
+            if self.stop.load(Ordering::Acquire) {
+                return
+            }
+            self.wake.error(format!("Lost connection to {}: {e}, reconnecting", self.address));
+            std::thread::sleep(retry);
+        }
+    }
+
+    /// Connect and receive until the connection is lost. Only returns Ok once the receiver has gone away
+    fn receive(&self, progress: &mut Progress) -> io::Result<()> {
+        let stream = TcpStream::connect(&self.address)?;
+        stream.set_read_timeout(Some(HEARTBEAT * 3))?;
+        *self.socket.lock().unwrap() = Some(stream.try_clone()?);
+        // Stopped while connecting, the socket wasn't there to shut down yet
+        if self.stop.load(Ordering::Acquire) {
+            return Ok(())
+        }
+        writeln!(&stream, "RESUME {} {}", progress.session.as_deref().unwrap_or("-"), progress.offset)?;
+        log::info!("connected to {}, resuming at {}", self.address, progress.offset);
+
+        let mut reader = BufReader::new(stream);
+        let mut message = Vec::new();
+        loop {
+            message.clear();
+            reader.read_until(b'\n', &mut message)?;
+            if !message.ends_with(b"\n") {
+                return Err(io::Error::new(ErrorKind::UnexpectedEof, "connection closed"))
+            }
+            let message = String::from_utf8_lossy(&message);
+
+            let line = if message.trim_end() == "PING" {
+                continue
+            } else if let Some(args) = message.strip_prefix("SESSION ") {
+                let (session, start) = parse_position(args).ok_or_else(|| invalid(&message))?;
+                if session == progress.session && start == progress.offset {
+                    continue
+                }
+                let restarted = progress.session.is_some();
+                *progress = Progress { session, offset: start };
+                if !restarted {
+                    continue
+                }
+                SourceLine::Restarted
+            } else if let Some((end, line)) = message.strip_prefix("LINE ").and_then(|args| args.split_once(' ')) {
+                progress.offset = end.parse().map_err(|_| invalid(&message))?;
+                SourceLine::Line(line.to_string())
+            } else {
+                return Err(invalid(&message))
+            };
+
+            if self.sender.send(line).is_err() {
+                return Ok(())
+            }
+            self.wake.wake();
+        }
+    }
+}
+
+impl LogSource for RemoteSource {
+    fn describe(&self) -> String {
+        self.address.clone()
+    }
+
+    /// Connects in the background, retrying every `poll_duration`
+    fn start(&mut self, wake: Wake, poll_duration: Duration) -> bool {
+        self.wake.set(wake);
+        if self.lines.is_none() {
+            let (sender, receiver) = std::sync::mpsc::channel();
+            self.lines = Some(receiver);
+            self.stop = Arc::new(AtomicBool::new(false));
+            self.socket = Arc::new(Mutex::new(None));
+            let connection = Connection {
+                address: self.address.clone(),
+                sender,
+                wake: self.wake.clone(),
+                stop: self.stop.clone(),
+                socket: self.socket.clone(),
+            };
+            std::thread::spawn(move || connection.run(poll_duration));
+        }
+        true
+    }
+
+    fn next_line(&mut self) -> io::Result<SourceLine> {
+        let Some(lines) = self.lines.as_ref() else { return Ok(SourceLine::End) };
+        Ok(self.wake.recv(lines))
+    }
+
+    /// Reconnect and receive the log from the beginning again
+    fn reset(&mut self) {
+        self.stop_thread();
+    }
+}
+
+impl Drop for RemoteSource {
+    fn drop(&mut self) {
+        self.stop_thread();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{LogReader, LogReaderOptions, LogReaderStatus};
+
+    const DIVE_START: &str = "0T23:24:45 80 I Party run start triggered - solo party: False\n";
+
+    fn read_message(reader: &mut impl BufRead) -> String {
+        let mut message = String::new();
+        reader.read_line(&mut message).unwrap();
+        message
+    }
+
+    fn wait_for(mut condition: impl FnMut() -> bool) -> bool {
+        for _ in 0..100 {
+            if condition() {
+                return true
+            }
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        false
+    }
+
+    #[test]
+    fn forward_and_resume() {
+        let path = std::env::temp_dir().join(format!("logreader_forward_{}.log", std::process::id()));
+        std::fs::write(&path, "first\nsecond\n").unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let forwarder = Forwarder::new(path.to_string_lossy()).poll_duration(Duration::from_millis(10));
+        std::thread::spawn(move || forwarder.serve(listener));
+
+        let stream = TcpStream::connect(address).unwrap();
+        writeln!(&stream, "RESUME - 0").unwrap();
+        let mut reader = BufReader::new(stream);
+        let session = read_message(&mut reader);
+        let (Some(session), 0) = parse_position(session.strip_prefix("SESSION ").unwrap()).unwrap() else { panic!("{session}") };
+        assert_eq!(read_message(&mut reader), "LINE 6 first\n");
+        assert_eq!(read_message(&mut reader), "LINE 13 second\n");
+        drop(reader);
+
+        // Resumes after the last line received, including lines written while disconnected
+        std::fs::OpenOptions::new().append(true).open(&path).unwrap().write_all(b"third\n").unwrap();
+        let stream = TcpStream::connect(address).unwrap();
+        writeln!(&stream, "RESUME {session} 6").unwrap();
+        let mut reader = BufReader::new(stream);
+        assert_eq!(read_message(&mut reader), format!("SESSION {session} 6\n"));
+        assert_eq!(read_message(&mut reader), "LINE 13 second\n");
+        assert_eq!(read_message(&mut reader), "LINE 19 third\n");
+
+        // A session that doesn't match the current log starts over
+        let stream = TcpStream::connect(address).unwrap();
+        writeln!(&stream, "RESUME other 6").unwrap();
+        let mut reader = BufReader::new(stream);
+        assert_eq!(read_message(&mut reader), format!("SESSION {session} 0\n"));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn resume_partway() {
+        let path = std::env::temp_dir().join(format!("logreader_forward_partial_{}.log", std::process::id()));
+        std::fs::write(&path, "first\nsec").unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let forwarder = Forwarder::new(path.to_string_lossy()).poll_duration(Duration::from_millis(10));
+        std::thread::spawn(move || forwarder.serve(listener));
+
+        let stream = TcpStream::connect(address).unwrap();
+        writeln!(&stream, "RESUME - 0").unwrap();
+        let mut reader = BufReader::new(stream);
+        let session = read_message(&mut reader);
+        let (Some(session), 0) = parse_position(session.strip_prefix("SESSION ").unwrap()).unwrap() else { panic!("{session}") };
+        assert_eq!(read_message(&mut reader), "LINE 6 first\n");
+        drop(reader);
+
+        // Resume while the game is still writing the next line, offsets continue from the resumed position
+        let stream = TcpStream::connect(address).unwrap();
+        writeln!(&stream, "RESUME {session} 6").unwrap();
+        let mut reader = BufReader::new(stream);
+        assert_eq!(read_message(&mut reader), format!("SESSION {session} 6\n"));
+        std::thread::sleep(Duration::from_millis(50));
+        std::fs::OpenOptions::new().append(true).open(&path).unwrap().write_all(b"ond\nthird\n").unwrap();
+        let mut message = read_message(&mut reader);
+        while message == "PING\n" {
+            message = read_message(&mut reader);
+        }
+        assert_eq!(message, "LINE 13 second\n");
+        assert_eq!(read_message(&mut reader), "LINE 19 third\n");
+        drop(reader);
+
+        // Partway through a line can't be resumed from without sending half a line, so it starts over
+        let stream = TcpStream::connect(address).unwrap();
+        writeln!(&stream, "RESUME {session} 9").unwrap();
+        let mut reader = BufReader::new(stream);
+        assert_eq!(read_message(&mut reader), format!("SESSION {session} 0\n"));
+        assert_eq!(read_message(&mut reader), "LINE 6 first\n");
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn remote_source_reconnects() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let options = LogReaderOptions::default().poll_duration(Duration::from_millis(20));
+        let reader = LogReader::new(RemoteSource::new(address.to_string()), options);
+
+        let (stream, _) = listener.accept().unwrap();
+        let mut requests = BufReader::new(stream.try_clone().unwrap());
+        assert_eq!(read_message(&mut requests), "RESUME - 0\n");
+        write!(&stream, "SESSION a 0\nLINE 70 {DIVE_START}").unwrap();
+        assert!(wait_for(|| reader.snapshot().dives.len() == 1));
+        stream.shutdown(Shutdown::Both).unwrap();
+
+        let (stream, _) = listener.accept().unwrap();
+        let mut requests = BufReader::new(stream.try_clone().unwrap());
+        assert_eq!(read_message(&mut requests), "RESUME a 70\n");
+        write!(&stream, "SESSION a 70\nLINE 140 {DIVE_START}").unwrap();
+        assert!(wait_for(|| reader.snapshot().dives.len() == 2));
+        assert!(wait_for(|| reader.get_status() == LogReaderStatus::Idle));
+
+        // The game restarted on the other end
+        write!(&stream, "SESSION b 0\nLINE 70 {DIVE_START}").unwrap();
+        assert!(wait_for(|| reader.snapshot().dives.len() == 3));
+        assert_eq!(reader.snapshot().sessions.len(), 2);
+    }
+}
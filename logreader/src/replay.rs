@@ -0,0 +1,249 @@
+use std::{
+    io,
+    sync::{
+        Arc,
+        Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::{Duration, Instant},
+};
+
+use inkbound_parser::parser::line_time;
+
+use crate::source::{LogSource, SourceLine, Wake};
+
+/// How often a running replay checks for lines that are due
+const TICK: Duration = Duration::from_millis(50);
+
+struct ReplayClock {
+    speed: f64,
+    paused: bool,
+    /// Seconds into the log, as of `since`
+    position: f64,
+    since: Instant,
+    /// Seconds from the first timestamp in the log to the last
+    duration: f64,
+}
+
+impl ReplayClock {
+    fn position(&self) -> f64 {
+        let elapsed = match self.paused {
+            false => self.since.elapsed().as_secs_f64() * self.speed,
+            true => 0.,
+        };
+        (self.position + elapsed).min(self.duration)
+    }
+
+    /// Fold the time run so far into the position, e.g. before changing speed
+    fn settle(&mut self) {
+        self.position = self.position();
+        self.since = Instant::now();
+    }
+}
+
+/// Handle for pausing, seeking and changing the speed of a replay while it's being read
+#[derive(Clone)]
+pub struct ReplayControl {
+    clock: Arc<Mutex<ReplayClock>>,
+}
+
+impl ReplayControl {
+    pub fn is_paused(&self) -> bool {
+        self.clock.lock().unwrap().paused
+    }
+
+    pub fn set_paused(&self, paused: bool) {
+        let mut clock = self.clock.lock().unwrap();
+        clock.settle();
+        clock.paused = paused;
+    }
+
+    pub fn speed(&self) -> f64 {
+        self.clock.lock().unwrap().speed
+    }
+
+    /// Multiplier on the original timing, e.g. 2.0 replays twice as fast
+    pub fn set_speed(&self, speed: f64) {
+        let mut clock = self.clock.lock().unwrap();
+        clock.settle();
+        clock.speed = speed.max(0.);
+    }
+
+    /// How far into the log the replay is
+    pub fn position(&self) -> Duration {
+        Duration::from_secs_f64(self.clock.lock().unwrap().position())
+    }
+
+    /// Time between the first and last line of the log
+    pub fn duration(&self) -> Duration {
+        Duration::from_secs_f64(self.clock.lock().unwrap().duration)
+    }
+
+    /// Jump to a point in the log. Seeking backwards reads the log again from the start, up to that point
+    pub fn seek(&self, position: Duration) {
+        let mut clock = self.clock.lock().unwrap();
+        clock.settle();
+        clock.position = position.as_secs_f64().min(clock.duration);
+    }
+}
+
+/// Feeds a finished log to the LogReader with its original timing, from the timestamps on each line
+pub struct ReplaySource {
+    description: String,
+    /// Lines with their time in seconds since the first line
+    lines: Vec<(f64, String)>,
+    next: usize,
+    control: ReplayControl,
+    /// Stops the thread waking the reader when dropped
+    stop: Arc<AtomicBool>,
+}
+
+impl ReplaySource {
+    pub fn new(log: &str) -> Self {
+        let mut start = None;
+        let mut time = 0.;
+        let lines: Vec<(f64, String)> = log.lines().map(|line| {
+            // Lines without a timestamp happen at the same time as the previous one
+            if let Some(seconds) = line_time(line) {
+                let start = *start.get_or_insert(seconds);
+                time = (seconds - start) as f64;
+            }
+            (time, format!("{line}\n"))
+        }).collect();
+
+        let clock = ReplayClock {
+            speed: 1.,
+            paused: false,
+            position: 0.,
+            since: Instant::now(),
+            duration: time,
+        };
+        Self {
+            description: format!("replay of {} lines", lines.len()),
+            lines,
+            next: 0,
+            control: ReplayControl { clock: Arc::new(Mutex::new(clock)) },
+            stop: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Replay a log file
+    pub fn open(filepath: &str) -> io::Result<Self> {
+        let log = String::from_utf8_lossy(&std::fs::read(filepath)?).into_owned();
+        let mut source = Self::new(&log);
+        source.description = format!("replay of {filepath}");
+        Ok(source)
+    }
+
+    /// Multiplier on the original timing, can be changed later through `control`
+    pub fn speed(self, speed: f64) -> Self {
+        self.control.set_speed(speed);
+        self
+    }
+
+    /// Start paused, e.g. to seek somewhere first
+    pub fn paused(self, paused: bool) -> Self {
+        self.control.set_paused(paused);
+        self
+    }
+
+    pub fn control(&self) -> ReplayControl {
+        self.control.clone()
+    }
+}
+
+impl LogSource for ReplaySource {
+    fn describe(&self) -> String {
+        self.description.clone()
+    }
+
+    fn start(&mut self, wake: Wake, _poll_duration: Duration) -> bool {
+        // Lines become due as time passes rather than when something is written, so check regularly.
+        // Stops once the LogReader stops, as waking fails
+        let stop = self.stop.clone();
+        std::thread::spawn(move || {
+            while !stop.load(Ordering::Acquire) && wake.wake() {
+                std::thread::sleep(TICK);
+            }
+        });
+        true
+    }
+
+    fn next_line(&mut self) -> io::Result<SourceLine> {
+        let position = self.control.clock.lock().unwrap().position();
+        if self.next > 0 && self.lines[self.next - 1].0 > position {
+            self.next = 0;
+            return Ok(SourceLine::Rewound)
+        }
+        match self.lines.get(self.next) {
+            Some((time, line)) if *time <= position => {
+                self.next += 1;
+                Ok(SourceLine::Line(line.clone()))
+            },
+            Some(_) => Ok(SourceLine::Pending),
+            None => Ok(SourceLine::End),
+        }
+    }
+
+    /// Start the replay over from the beginning
+    fn reset(&mut self) {
+        self.next = 0;
+        self.control.seek(Duration::ZERO);
+    }
+
+    fn replay(&self) -> Option<ReplayControl> {
+        Some(self.control.clone())
+    }
+}
+
+impl Drop for ReplaySource {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOG: &str = "\
+0T23:00:00 80 I first
+0T23:00:10 80 I second
+continued without a timestamp
+0T23:01:00 80 I last
+";
+
+    fn line(text: &str) -> SourceLine {
+        SourceLine::Line(format!("{text}\n"))
+    }
+
+    #[test]
+    fn replay_timing() {
+        let mut source = ReplaySource::new(LOG).paused(true);
+        let control = source.control();
+        assert_eq!(control.duration(), Duration::from_secs(60));
+        assert_eq!(source.next_line().unwrap(), line("0T23:00:00 80 I first"));
+        assert_eq!(source.next_line().unwrap(), SourceLine::Pending);
+
+        control.seek(Duration::from_secs(30));
+        assert_eq!(source.next_line().unwrap(), line("0T23:00:10 80 I second"));
+        assert_eq!(source.next_line().unwrap(), line("continued without a timestamp"));
+        assert_eq!(source.next_line().unwrap(), SourceLine::Pending);
+
+        // Going back starts over, and only replays up to the new position
+        control.seek(Duration::from_secs(5));
+        assert_eq!(source.next_line().unwrap(), SourceLine::Rewound);
+        assert_eq!(source.next_line().unwrap(), line("0T23:00:00 80 I first"));
+        assert_eq!(source.next_line().unwrap(), SourceLine::Pending);
+
+        // A minute of log in a tenth of a second
+        control.set_speed(600.);
+        control.set_paused(false);
+        std::thread::sleep(Duration::from_millis(150));
+        assert_eq!(source.next_line().unwrap(), line("0T23:00:10 80 I second"));
+        assert_eq!(source.next_line().unwrap(), line("continued without a timestamp"));
+        assert_eq!(source.next_line().unwrap(), line("0T23:01:00 80 I last"));
+        assert_eq!(source.next_line().unwrap(), SourceLine::End);
+        assert_eq!(control.position(), control.duration());
+    }
+}
@@ -0,0 +1,781 @@
+use std::{
+    ffi::OsString,
+    fs::File,
+    io::{self, BufRead, BufReader, ErrorKind, Read, Seek},
+    net::TcpStream,
+    path::{Path, PathBuf},
+    sync::{
+        Arc,
+        RwLock,
+        atomic::{AtomicBool, Ordering},
+        mpsc::{Sender, Receiver, TryRecvError},
+    },
+    time::Duration,
+};
+
+use notify::{Watcher, RecursiveMode};
+use serde::{Serialize, Deserialize};
+
+use crate::{LogReaderCommand, ReplayControl, SharedState};
+
+/// Result of asking a LogSource for the next line
+#[derive(Debug, PartialEq)]
+pub enum SourceLine {
+    /// A complete line, including its line ending
+    Line(String),
+    /// Nothing more to read right now
+    Pending,
+    /// The log was replaced, e.g. by the game restarting. Lines from here on are from a new log
+    Restarted,
+    /// The source went back to the start of the same log, e.g. seeking backwards in a replay.
+    /// Everything read so far should be discarded
+    Rewound,
+    /// The log isn't available yet, e.g. the game has never been run
+    Waiting,
+    /// No more lines will come, e.g. stdin was closed or a replay finished
+    End,
+}
+
+/// How far a source has been read, to resume from later. See `LogSource::position`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SourcePosition {
+    /// First line of the log, to tell whether it's still the same log
+    pub header: String,
+    /// Identity of the file, as the first line alone may be the same for every log
+    pub file: String,
+    /// Byte offset just past the last line read
+    pub offset: u64,
+}
+
+/// Handed to a LogSource when started, to tell the reading thread there may be new lines
+#[derive(Clone)]
+pub struct Wake {
+    pub(crate) sender: Sender<LogReaderCommand>,
+    pub(crate) shared: SharedState,
+}
+
+impl Wake {
+    /// Ask the reading thread to read. Returns false once the LogReader has stopped
+    pub fn wake(&self) -> bool {
+        self.sender.send(LogReaderCommand::Update).is_ok()
+    }
+
+    /// Report an error from outside of `next_line`, e.g. a background thread, shown in the settings window
+    pub fn error(&self, message: String) {
+        self.shared.set_error(message);
+    }
+}
+
+/// Wakes the current reading thread from a source's background thread, at most once per catching up
+#[derive(Clone, Default)]
+pub(crate) struct SharedWake {
+    /// Replaced when the LogReader is reset
+    wake: Arc<RwLock<Option<Wake>>>,
+    /// Whether the reading thread has been woken and not yet caught up, to avoid waking it for every line
+    woken: Arc<AtomicBool>,
+}
+
+impl SharedWake {
+    pub(crate) fn set(&self, wake: Wake) {
+        *self.wake.write().unwrap() = Some(wake);
+        self.woken.store(false, Ordering::Release);
+    }
+
+    pub(crate) fn wake(&self) {
+        if !self.woken.swap(true, Ordering::AcqRel) {
+            if let Some(wake) = self.wake.read().unwrap().as_ref() {
+                wake.wake();
+            }
+        }
+    }
+
+    pub(crate) fn error(&self, message: String) {
+        if let Some(wake) = self.wake.read().unwrap().as_ref() {
+            wake.error(message);
+        }
+    }
+
+    /// Get the next line received by a background thread, or Pending once caught up
+    pub(crate) fn recv(&self, lines: &Receiver<SourceLine>) -> SourceLine {
+        match lines.try_recv() {
+            Ok(line) => line,
+            Err(TryRecvError::Empty) => {
+                // Try again after clearing, in case a line arrived without waking as it was still set
+                self.woken.store(false, Ordering::Release);
+                match lines.try_recv() {
+                    Ok(line) => line,
+                    Err(TryRecvError::Empty) => SourceLine::Pending,
+                    Err(TryRecvError::Disconnected) => SourceLine::End,
+                }
+            },
+            Err(TryRecvError::Disconnected) => SourceLine::End,
+        }
+    }
+}
+
+/// Where a LogReader gets its lines from
+pub trait LogSource: Send {
+    /// Shown to the user, e.g. the file path
+    fn describe(&self) -> String;
+
+    /// Called before reading starts, and again after the LogReader is reset.
+    /// Call `wake` whenever new lines may be available. Return false if the source can't do that reliably,
+    ///  and should also be polled every `poll_duration`
+    fn start(&mut self, wake: Wake, poll_duration: Duration) -> bool;
+
+    /// Get the next line without blocking
+    fn next_line(&mut self) -> io::Result<SourceLine>;
+
+    /// Start over from the beginning, if possible, after the LogReader is reset.
+    /// Sources that can't go back just continue from where they are
+    fn reset(&mut self) {}
+
+    /// Where reading is up to, if the source can resume from there later
+    fn position(&self) -> Option<SourcePosition> {
+        None
+    }
+
+    /// Continue from a position saved earlier, before being started. Returns false if that's no longer possible,
+    ///  e.g. the log has been replaced since, and the source then starts as usual
+    fn resume(&mut self, _position: &SourcePosition) -> bool {
+        false
+    }
+
+    /// Controls for pausing and seeking, if this is a replay
+    fn replay(&self) -> Option<ReplayControl> {
+        None
+    }
+
+    /// Byte offset in the log reading is up to, for sources that read from a file, see `file`
+    fn offset(&self) -> Option<u64> {
+        None
+    }
+
+    /// The file being read, if any, so its size can be checked without waiting on the reading thread
+    fn file(&self) -> Option<PathBuf> {
+        None
+    }
+
+    /// How changes to the log are noticed, for sources that watch a file
+    fn watcher(&self) -> Option<WatcherBackend> {
+        None
+    }
+
+    /// Change how changes to the log are noticed, used from the next `start`
+    fn set_watcher(&mut self, _backend: WatcherBackend) {}
+
+    /// Bytes the last line took up in the log, if that may differ from its length, e.g. once invalid UTF-8 is replaced
+    fn line_bytes(&self) -> Option<u64> {
+        None
+    }
+}
+
+/// Identifies the file behind a path, to notice when the game replaces the log instead of appending to it
+#[derive(Debug, PartialEq)]
+pub(crate) struct FileIdentity {
+    #[cfg(unix)]
+    inode: (u64, u64),
+    // NOTE: windows may reuse the creation time of a file recreated quickly under the same name,
+    //  the new log is then usually caught by being shorter than the old one
+    created: Option<std::time::SystemTime>,
+}
+
+impl FileIdentity {
+    fn of(metadata: &std::fs::Metadata) -> Self {
+        Self {
+            #[cfg(unix)]
+            inode: {
+                use std::os::unix::fs::MetadataExt;
+                (metadata.dev(), metadata.ino())
+            },
+            created: metadata.created().ok(),
+        }
+    }
+
+    /// Short string that stays the same as long as the file isn't replaced
+    pub(crate) fn id(&self) -> String {
+        let created = self.created
+            .and_then(|created| created.duration_since(std::time::UNIX_EPOCH).ok())
+            .map_or(0, |created| created.as_nanos());
+        #[cfg(unix)]
+        return format!("{}-{}-{created}", self.inode.0, self.inode.1);
+        #[cfg(not(unix))]
+        return format!("{created}");
+    }
+}
+
+/// The log file currently being read
+pub(crate) struct OpenLog {
+    pub(crate) reader: BufReader<File>,
+    pub(crate) identity: FileIdentity,
+    /// Start of a line the game hasn't finished writing yet
+    pending: Vec<u8>,
+    /// Byte offset just past the last complete line read
+    pub(crate) offset: u64,
+    /// First line of the file, once read
+    header: Option<String>,
+}
+
+impl OpenLog {
+    pub(crate) fn open(filepath: &str) -> io::Result<Self> {
+        let file = File::open(filepath)?;
+        let identity = FileIdentity::of(&file.metadata()?);
+        Ok(Self {
+            reader: BufReader::new(file),
+            identity,
+            pending: Vec::new(),
+            offset: 0,
+            header: None,
+        })
+    }
+
+    /// Continue reading from a byte offset, which should be the start of a line
+    pub(crate) fn seek(&mut self, offset: io::SeekFrom) -> io::Result<()> {
+        self.offset = self.reader.seek(offset)?;
+        self.pending.clear();
+        Ok(())
+    }
+
+    /// Whether a byte offset is the start of a line, i.e. the start of the file or just after a newline.
+    /// Moves the read position, so seek afterwards
+    pub(crate) fn line_start(&mut self, offset: u64) -> io::Result<bool> {
+        if offset == 0 {
+            return Ok(true)
+        }
+        self.seek(io::SeekFrom::Start(offset - 1))?;
+        let mut byte = [0];
+        self.reader.read_exact(&mut byte)?;
+        Ok(byte[0] == b'\n')
+    }
+
+    /// Read the first line of the file into `header`, if it has been written yet. Leaves the file positioned after it
+    fn read_header(&mut self) -> io::Result<()> {
+        if self.header.is_none() {
+            self.seek(io::SeekFrom::Start(0))?;
+            self.next_line()?;
+        }
+        Ok(())
+    }
+
+    /// Read the next complete line, if there is one.
+    /// An incomplete line at the end of the file is held back until the rest of it is written,
+    ///  and invalid UTF-8 is replaced rather than stopping the read.
+    pub(crate) fn next_line(&mut self) -> io::Result<Option<String>> {
+        self.reader.read_until(b'\n', &mut self.pending)?;
+        if !self.pending.ends_with(b"\n") {
+            return Ok(None)
+        }
+        let line = String::from_utf8_lossy(&self.pending).into_owned();
+        if self.offset == 0 {
+            self.header = Some(line.trim_end().to_string());
+        }
+        self.offset += self.pending.len() as u64;
+        self.pending.clear();
+        Ok(Some(line))
+    }
+
+    /// Check whether the file at the path is no longer the one being read, or has been truncated.
+    /// Returns the reason, if so.
+    pub(crate) fn changed(&mut self, filepath: &str) -> io::Result<Option<&'static str>> {
+        let metadata = std::fs::metadata(filepath)?;
+        if FileIdentity::of(&metadata) != self.identity {
+            Ok(Some("replaced"))
+        } else if metadata.len() < self.reader.stream_position()? {
+            Ok(Some("truncated"))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+/// How a FileSource notices the log changing
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum WatcherBackend {
+    /// The platform's change notifications, e.g. inotify on Linux. Falls back to polling if they're unavailable or fail.
+    /// The log is still checked every poll duration, as notifications can be missed,
+    ///  e.g. on Windows for a file another process keeps open and appends to
+    #[default]
+    Native,
+    /// Check the log for changes every poll duration
+    Poll,
+}
+
+/// Handle change notifications for the log. Errors from a native watcher set `failed` so the source can fall back to polling,
+///  other errors are reported
+fn watch_handler(wake: Wake, filename: Option<OsString>, failed: Option<Arc<AtomicBool>>) -> impl FnMut(notify::Result<notify::Event>) + Send + 'static {
+    move |event: notify::Result<notify::Event>| {
+        match event {
+            Ok(event) => {
+                // Ignore other files in the same directory
+                if !event.paths.is_empty() && !event.paths.iter().any(|p| p.file_name() == filename.as_deref()) {
+                    return
+                }
+                log::trace!("file update received");
+                if !wake.wake() {
+                    log::error!("Error sending update to logging thread");
+                }
+            },
+            Err(e) => match failed.as_ref() {
+                Some(failed) => {
+                    log::warn!("native file watcher failed, falling back to polling: {e}");
+                    failed.store(true, Ordering::Release);
+                    wake.wake();
+                },
+                None => wake.error(format!("Error watching log file: {e}")),
+            },
+        }
+    }
+}
+
+/// Watch the directory containing the log rather than the log itself, so that it can be picked up once created,
+///  and so replacing it is noticed
+fn start_watcher(wake: Wake, filepath: &str, backend: WatcherBackend, poll_duration: Duration, failed: &Arc<AtomicBool>) -> notify::Result<Box<dyn Watcher + Send>> {
+    let path = Path::new(filepath);
+    let filename = path.file_name().map(|f| f.to_os_string());
+    let directory = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+
+    if backend == WatcherBackend::Native {
+        let native = notify::recommended_watcher(watch_handler(wake.clone(), filename.clone(), Some(failed.clone())))
+            .and_then(|mut watcher| {
+                watcher.watch(directory, RecursiveMode::NonRecursive)?;
+                Ok(watcher)
+            });
+        match native {
+            Ok(watcher) => return Ok(Box::new(watcher)),
+            Err(e) => log::warn!("unable to watch {} natively, polling instead: {e}", directory.display()),
+        }
+    }
+
+    let mut watcher = notify::PollWatcher::new(watch_handler(wake, filename, None), notify::Config::default().with_poll_interval(poll_duration))?;
+    watcher.watch(directory, RecursiveMode::NonRecursive)?;
+    Ok(Box::new(watcher))
+}
+
+/// Follows a log file as the game writes to it, reopening it when the game replaces it
+pub struct FileSource {
+    filepath: String,
+    skip_current: bool,
+    log: Option<OpenLog>,
+    /// Whether the next log opened replaces one that was already read
+    replaced: bool,
+    /// Check whether the file was replaced before reading more, set after catching up to the end
+    check: bool,
+    watcher: Option<Box<dyn Watcher + Send>>,
+    backend: WatcherBackend,
+    /// Set when the native watcher fails, to replace it with a polling one
+    watcher_failed: Arc<AtomicBool>,
+    /// Kept from `start` to restart the watcher with
+    wake: Option<Wake>,
+    poll_duration: Duration,
+    /// Bytes the last line read took up in the file
+    line_bytes: u64,
+}
+
+impl FileSource {
+    pub fn new(filepath: impl Into<String>) -> Self {
+        Self {
+            filepath: filepath.into(),
+            skip_current: false,
+            log: None,
+            replaced: false,
+            check: false,
+            watcher: None,
+            backend: WatcherBackend::default(),
+            watcher_failed: Arc::new(AtomicBool::new(false)),
+            wake: None,
+            poll_duration: Duration::ZERO,
+            line_bytes: 0,
+        }
+    }
+
+    /// How to watch the log for changes
+    pub fn watcher(self, backend: WatcherBackend) -> Self {
+        Self {
+            backend,
+            ..self
+        }
+    }
+
+    /// Replace a native watcher that has failed with a polling one
+    fn fall_back_to_polling(&mut self) {
+        let Some(wake) = self.wake.clone() else { return };
+        self.backend = WatcherBackend::Poll;
+        self.watcher = None;
+        match start_watcher(wake.clone(), &self.filepath, self.backend, self.poll_duration, &self.watcher_failed) {
+            Ok(watcher) => self.watcher = Some(watcher),
+            Err(e) => wake.error(format!("Unable to watch {} for changes: {e}", self.filepath)),
+        }
+    }
+
+    /// Skip over anything already in the log, and only read lines written from now on.
+    /// Only applies when first started, not after a reset
+    pub fn skip_current(self, skip_current: bool) -> Self {
+        Self {
+            skip_current,
+            ..self
+        }
+    }
+}
+
+impl LogSource for FileSource {
+    fn describe(&self) -> String {
+        self.filepath.clone()
+    }
+
+    fn start(&mut self, wake: Wake, poll_duration: Duration) -> bool {
+        if std::mem::take(&mut self.skip_current) {
+            // If it doesn't exist yet, anything written once it does is new, so there's nothing to skip
+            if let Ok(mut log) = OpenLog::open(&self.filepath) {
+                // Still read the header, so the position can be checkpointed
+                match log.read_header().and_then(|()| log.seek(io::SeekFrom::End(0))) {
+                    Ok(()) => self.log = Some(log),
+                    Err(e) => wake.error(format!("Unable to skip to the end of {}: {e}", self.filepath)),
+                }
+            }
+        }
+
+        // Replace any previous watcher, it would wake the previous reading thread
+        self.watcher = None;
+        self.watcher_failed.store(false, Ordering::Release);
+        self.wake = Some(wake.clone());
+        self.poll_duration = poll_duration;
+        match start_watcher(wake, &self.filepath, self.backend, poll_duration, &self.watcher_failed) {
+            Ok(watcher) => {
+                self.watcher = Some(watcher);
+                // Native notifications can be missed, so only rely on them alone when polling
+                self.backend == WatcherBackend::Poll
+            },
+            Err(e) => {
+                // e.g. the game's directory doesn't exist yet
+                log::warn!("unable to watch for changes to {}, polling instead: {e}", self.filepath);
+                false
+            },
+        }
+    }
+
+    fn next_line(&mut self) -> io::Result<SourceLine> {
+        if self.watcher_failed.swap(false, Ordering::AcqRel) {
+            self.fall_back_to_polling();
+        }
+        // The game recreates the log when it restarts, so start over from the beginning of the new one
+        if let (true, Some(log)) = (std::mem::take(&mut self.check), self.log.as_mut()) {
+            match log.changed(&self.filepath) {
+                Ok(None) => (),
+                Ok(Some(reason)) => {
+                    log::info!("log file was {reason}, reopening");
+                    self.log = None;
+                    self.replaced = true;
+                },
+                Err(e) if e.kind() == ErrorKind::NotFound => {
+                    log::info!("log file was removed");
+                    self.log = None;
+                    self.replaced = true;
+                },
+                Err(e) => {
+                    self.check = true;
+                    return Err(e)
+                },
+            }
+        }
+
+        if self.log.is_none() {
+            match OpenLog::open(&self.filepath) {
+                Ok(log) => {
+                    log::info!("opened log file {}", self.filepath);
+                    self.log = Some(log);
+                    if std::mem::take(&mut self.replaced) {
+                        return Ok(SourceLine::Restarted)
+                    }
+                },
+                Err(e) if e.kind() == ErrorKind::NotFound => return Ok(SourceLine::Waiting),
+                Err(e) => return Err(io::Error::new(e.kind(), format!("Unable to open {}: {e}", self.filepath))),
+            }
+        }
+        let Some(log) = self.log.as_mut() else { return Ok(SourceLine::Waiting) };
+
+        let offset = log.offset;
+        match log.next_line()? {
+            Some(line) => {
+                self.line_bytes = log.offset - offset;
+                Ok(SourceLine::Line(line))
+            },
+            None => {
+                self.check = true;
+                Ok(SourceLine::Pending)
+            },
+        }
+    }
+
+    fn reset(&mut self) {
+        self.log = None;
+        self.replaced = false;
+        self.check = false;
+    }
+
+    fn position(&self) -> Option<SourcePosition> {
+        let log = self.log.as_ref()?;
+        Some(SourcePosition {
+            header: log.header.clone()?,
+            file: log.identity.id(),
+            offset: log.offset,
+        })
+    }
+
+    fn offset(&self) -> Option<u64> {
+        Some(self.log.as_ref()?.offset)
+    }
+
+    fn watcher(&self) -> Option<WatcherBackend> {
+        Some(self.backend)
+    }
+
+    fn set_watcher(&mut self, backend: WatcherBackend) {
+        self.backend = backend;
+    }
+
+    fn file(&self) -> Option<PathBuf> {
+        Some(PathBuf::from(&self.filepath))
+    }
+
+    fn line_bytes(&self) -> Option<u64> {
+        Some(self.line_bytes)
+    }
+
+    fn resume(&mut self, position: &SourcePosition) -> bool {
+        // Skipping means ignoring everything before now, including what was read last time
+        if self.skip_current {
+            return false
+        }
+        let resumed = OpenLog::open(&self.filepath).and_then(|mut log| {
+            let length = log.reader.get_ref().metadata()?.len();
+            log.read_header()?;
+            if log.identity.id() != position.file || log.header.as_ref() != Some(&position.header) || position.offset > length {
+                return Ok(None)
+            }
+            log.seek(io::SeekFrom::Start(position.offset))?;
+            Ok(Some(log))
+        });
+        match resumed {
+            Ok(Some(log)) => {
+                self.log = Some(log);
+                true
+            },
+            Ok(None) => false,
+            Err(e) => {
+                log::warn!("unable to resume reading {}: {e}", self.filepath);
+                false
+            },
+        }
+    }
+}
+
+/// Reads lines from any stream on a background thread, e.g. stdin or a socket
+pub struct StreamSource {
+    description: String,
+    reader: Option<Box<dyn Read + Send>>,
+    lines: Option<Receiver<SourceLine>>,
+    wake: SharedWake,
+    /// Socket to shut down when dropped, to stop the background thread
+    socket: Option<TcpStream>,
+}
+
+impl StreamSource {
+    pub fn new(reader: impl Read + Send + 'static, description: impl Into<String>) -> Self {
+        Self {
+            description: description.into(),
+            reader: Some(Box::new(reader)),
+            lines: None,
+            wake: SharedWake::default(),
+            socket: None,
+        }
+    }
+
+    pub fn stdin() -> Self {
+        Self::new(io::stdin(), "stdin")
+    }
+
+    /// Connect to a TCP socket that sends raw log lines, see `RemoteSource` for receiving from `Forwarder`
+    pub fn tcp(address: &str) -> io::Result<Self> {
+        let stream = TcpStream::connect(address)?;
+        let socket = stream.try_clone()?;
+        let mut source = Self::new(stream, format!("tcp://{address}"));
+        source.socket = Some(socket);
+        Ok(source)
+    }
+
+    fn spawn_reader(&mut self, reader: Box<dyn Read + Send>) {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        self.lines = Some(receiver);
+        let description = self.description.clone();
+        let wake = self.wake.clone();
+
+        std::thread::spawn(move || {
+            // Lines are only complete once their newline arrives, or the stream ends
+            let mut reader = BufReader::new(reader);
+            let mut line = Vec::new();
+            loop {
+                line.clear();
+                match reader.read_until(b'\n', &mut line) {
+                    Ok(0) => break,
+                    Ok(_) => {
+                        if sender.send(SourceLine::Line(String::from_utf8_lossy(&line).into_owned())).is_err() {
+                            return
+                        }
+                        wake.wake();
+                    },
+                    Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+                    Err(e) => {
+                        wake.error(format!("Error reading from {description}: {e}"));
+                        break
+                    },
+                }
+            }
+            // Wake once more so the end of the stream is noticed
+            sender.send(SourceLine::End).ok();
+            wake.wake();
+        });
+    }
+}
+
+impl LogSource for StreamSource {
+    fn describe(&self) -> String {
+        self.description.clone()
+    }
+
+    fn start(&mut self, wake: Wake, _poll_duration: Duration) -> bool {
+        self.wake.set(wake);
+        if let Some(reader) = self.reader.take() {
+            self.spawn_reader(reader);
+        }
+        true
+    }
+
+    fn next_line(&mut self) -> io::Result<SourceLine> {
+        let Some(lines) = self.lines.as_ref() else { return Ok(SourceLine::End) };
+        Ok(self.wake.recv(lines))
+    }
+}
+
+impl Drop for StreamSource {
+    fn drop(&mut self) {
+        if let Some(socket) = self.socket.as_ref() {
+            socket.shutdown(std::net::Shutdown::Both).ok();
+        }
+    }
+}
+
+/// A complete log held in memory, e.g. for tests or a log loaded from elsewhere
+pub struct MemorySource {
+    lines: Vec<String>,
+    next: usize,
+}
+
+impl MemorySource {
+    pub fn new(log: &str) -> Self {
+        Self {
+            lines: log.lines().map(|line| format!("{line}\n")).collect(),
+            next: 0,
+        }
+    }
+}
+
+impl LogSource for MemorySource {
+    fn describe(&self) -> String {
+        format!("{} lines in memory", self.lines.len())
+    }
+
+    fn start(&mut self, _wake: Wake, _poll_duration: Duration) -> bool {
+        // Everything is read on the first update
+        true
+    }
+
+    fn next_line(&mut self) -> io::Result<SourceLine> {
+        match self.lines.get(self.next) {
+            Some(line) => {
+                self.next += 1;
+                Ok(SourceLine::Line(line.clone()))
+            },
+            None => Ok(SourceLine::End),
+        }
+    }
+
+    fn reset(&mut self) {
+        self.next = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn append(path: &std::path::Path, data: &[u8]) {
+        std::fs::OpenOptions::new().append(true).open(path).unwrap().write_all(data).unwrap();
+    }
+
+    #[test]
+    fn partial_lines() {
+        let path = std::env::temp_dir().join(format!("logreader_partial_{}.log", std::process::id()));
+        std::fs::write(&path, "complete line\nhalf of a ").unwrap();
+
+        let mut log = OpenLog::open(path.to_str().unwrap()).unwrap();
+        assert_eq!(log.next_line().unwrap().as_deref(), Some("complete line\n"));
+        assert_eq!(log.next_line().unwrap(), None);
+
+        append(&path, b"line\ninvalid \xff utf8\n");
+        assert_eq!(log.next_line().unwrap().as_deref(), Some("half of a line\n"));
+        assert_eq!(log.next_line().unwrap().as_deref(), Some("invalid \u{FFFD} utf8\n"));
+        assert_eq!(log.next_line().unwrap(), None);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn detect_truncation_and_replacement() {
+        let path = std::env::temp_dir().join(format!("logreader_reopen_{}.log", std::process::id()));
+        let path_str = path.to_str().unwrap();
+        std::fs::write(&path, "first line\nsecond line\n").unwrap();
+
+        let mut log = OpenLog::open(path_str).unwrap();
+        while log.next_line().unwrap().is_some() {}
+        assert_eq!(log.changed(path_str).unwrap(), None);
+
+        // Appending is the normal case
+        append(&path, b"third line\n");
+        assert_eq!(log.changed(path_str).unwrap(), None);
+
+        std::fs::OpenOptions::new().write(true).open(&path).unwrap().set_len(4).unwrap();
+        assert_eq!(log.changed(path_str).unwrap(), Some("truncated"));
+
+        // Recreated, even with more content than before
+        let mut log = OpenLog::open(path_str).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        std::fs::write(&path, "a much longer line in a brand new log file\n").unwrap();
+        #[cfg(unix)]
+        assert_eq!(log.changed(path_str).unwrap(), Some("replaced"));
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(log.changed(path_str).is_err());
+    }
+
+    #[test]
+    fn file_source_restarts() {
+        let path = std::env::temp_dir().join(format!("logreader_source_{}.log", std::process::id()));
+        let mut source = FileSource::new(path.to_string_lossy());
+        assert_eq!(source.next_line().unwrap(), SourceLine::Waiting);
+
+        std::fs::write(&path, "old\n").unwrap();
+        assert_eq!(source.next_line().unwrap(), SourceLine::Line("old\n".to_string()));
+        assert_eq!(source.next_line().unwrap(), SourceLine::Pending);
+
+        std::fs::write(&path, "").unwrap();
+        assert_eq!(source.next_line().unwrap(), SourceLine::Restarted);
+        append(&path, b"new\n");
+        assert_eq!(source.next_line().unwrap(), SourceLine::Line("new\n".to_string()));
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
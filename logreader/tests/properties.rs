@@ -0,0 +1,71 @@
+//! Property tests for reading a log while the game is still writing it.
+//!
+//! The game appends to its log in arbitrary pieces, which may end part way through a line or even a character,
+//! so reading it back a piece at a time must give exactly the same result as parsing the whole log at once.
+
+use std::io::Write;
+
+use inkbound_parser::{
+    generator::{LogGenerator, GeneratorOptions},
+    parser::{LogParser, DataLog},
+};
+use logreader::{FileSource, LogSource, SourceLine};
+use proptest::prelude::*;
+
+fn generated_log(seed: u64) -> String {
+    let options = GeneratorOptions::default()
+        .seed(seed)
+        .dives(2)
+        .combats(2)
+        .turns(3)
+        .noise(1);
+    // Multi-byte characters, so that some splits land inside one
+    LogGenerator::new(options).generate().replace("Player2", "Pläyer2")
+}
+
+/// Parse a whole log in one pass.
+/// Returned as a json Value, so HashMap ordering doesn't matter
+fn parse(log: &str) -> serde_json::Value {
+    let mut datalog = DataLog::new();
+    datalog.handle_events(LogParser::new().parse_lines(&log.lines().collect::<Vec<&str>>()));
+    serde_json::to_value(&datalog).unwrap()
+}
+
+/// Write a log to a file in pieces, splitting at each of the given byte offsets,
+/// and read back whatever lines are complete after each piece the way LogReader does
+fn read_chunked(log: &str, splits: &[usize], name: &str) -> serde_json::Value {
+    let mut splits: Vec<usize> = splits.iter().map(|s| s % (log.len() + 1)).collect();
+    splits.sort();
+    splits.push(log.len());
+
+    let path = std::env::temp_dir().join(format!("logreader_{name}_{}.log", std::process::id()));
+    let mut file = std::fs::File::create(&path).unwrap();
+    let mut source = FileSource::new(path.to_string_lossy());
+    let mut parser = LogParser::new();
+    let mut datalog = DataLog::new();
+    let mut start = 0;
+    for end in splits {
+        file.write_all(&log.as_bytes()[start..end]).unwrap();
+        file.flush().unwrap();
+        start = end;
+        loop {
+            match source.next_line().unwrap() {
+                SourceLine::Line(line) => datalog.handle_events(parser.parse_line(&line).into_iter().collect()),
+                SourceLine::Pending => break,
+                other => panic!("unexpected {other:?} while reading {}", path.display()),
+            }
+        }
+    }
+    std::fs::remove_file(&path).unwrap();
+    serde_json::to_value(&datalog).unwrap()
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(64))]
+
+    #[test]
+    fn chunked_equals_one_pass(seed in any::<u64>(), splits in prop::collection::vec(any::<usize>(), 0..20)) {
+        let log = generated_log(seed);
+        prop_assert_eq!(read_chunked(&log, &splits, "chunked"), parse(&log));
+    }
+}
@@ -4,16 +4,16 @@
     windows_subsystem = "windows"
 )]
 use clap::{command, arg};
-use inkbound_parser::parse_log_to_json;
+use inkbound_parser::{parse_log_to_json, merge_logs_to_json, diagnose_log, sanitize_log, aspects::{self, load_aspects, Aspect}, parser::{LogParserOptions, LinePatterns}, sanitize::SanitizeOptions, generator::{LogGenerator, GeneratorOptions, PlayerConfig, DEFAULT_PARTY}};
 
-use std::time::Duration;
+use std::{io::Write, sync::Arc, time::Duration};
 
-use logreader::LogReader;
+use logreader::{LogReader, LogReaderOptions, FileSource, WatcherBackend, StreamSource, Forwarder, RemoteSource, ReplaySource};
 
 #[inline(always)]
 fn default_logpath() -> String {
     // Use a local log file for test purposes
-    // TODO: consider committing some trimmed down, sanitized logs to the repo for test cases
+    // TODO: consider committing some trimmed down logs to the repo for test cases, see --sanitize
     #[cfg(debug_assertions)]
     return "./logfile.log".to_string();
 
@@ -27,6 +27,97 @@ fn default_logpath() -> String {
     }
 }
 
+/// Find an aspect by id, abbreviation or name, e.g. "C01", "MGM" or "Magma Miner"
+fn find_aspect(s: &str) -> Option<Aspect> {
+    let s = s.trim();
+    aspects::registry().iter()
+        .find(|info| [&info.id, &info.abbv, &info.name].iter().any(|v| v.eq_ignore_ascii_case(s)))
+        .map(|info| info.aspect())
+}
+
+/// A chance between 0.0 and 1.0
+fn parse_rate(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(rate) if (0.0..=1.0).contains(&rate) => Ok(rate),
+        _ => Err("must be a number from 0.0 to 1.0".to_string()),
+    }
+}
+
+/// Comma separated list, without empty entries
+fn split_list(s: &str) -> Vec<String> {
+    s.split(',').map(str::trim).filter(|s| !s.is_empty()).map(str::to_string).collect()
+}
+
+fn generator_options(matches: &clap::ArgMatches) -> GeneratorOptions {
+    let mut options = GeneratorOptions::default()
+        .seed(*matches.get_one::<u64>("seed").unwrap_or(&0))
+        .dives(*matches.get_one::<usize>("dives").unwrap_or(&1))
+        .combats(*matches.get_one::<usize>("combats").unwrap_or(&5));
+
+    let party = match matches.get_one::<String>("party") {
+        Some(party) => party.split(',').map(|aspect| find_aspect(aspect).unwrap_or_else(|| {
+            log::error!("unknown aspect: {aspect}");
+            std::process::exit(1);
+        })).collect(),
+        None => DEFAULT_PARTY.to_vec(),
+    };
+    let skills = matches.get_one::<String>("skills").map(|skills| split_list(skills));
+    let crit_rate = matches.get_one::<f64>("crit-rate");
+    let players = party.into_iter().enumerate().map(|(index, aspect)| {
+        let mut player = PlayerConfig::new(&format!("Player{}", index + 1), aspect);
+        if let Some(skills) = &skills {
+            player = player.skills(skills.clone());
+        }
+        if let Some(&crit_rate) = crit_rate {
+            player = player.crit_rate(crit_rate);
+        }
+        player
+    }).collect();
+    options = options.players(players);
+
+    if let Some(effects) = matches.get_one::<String>("status-effects") {
+        options = options.status_effects(split_list(effects));
+    }
+    if let Some(&status_rate) = matches.get_one::<f64>("status-rate") {
+        options = options.status_rate(status_rate);
+    }
+    if let Some(&orb_rate) = matches.get_one::<f64>("orb-rate") {
+        options = options.orb_rate(orb_rate);
+    }
+    options
+}
+
+/// Dives written to the demo log before it is started over, so it doesn't grow forever
+const DEMO_DIVES_PER_LOG: usize = 10;
+
+/// Keep appending generated dives to a file, a few lines at a time, as if a game were being played.
+/// Every few dives the file is recreated, as the game does when relaunched
+fn spawn_demo_writer(path: std::path::PathBuf, options: GeneratorOptions) {
+    std::thread::spawn(move || {
+        let mut generator = LogGenerator::new(options.noise(0));
+        loop {
+            let mut file = match std::fs::File::create(&path) {
+                Ok(file) => file,
+                Err(e) => {
+                    log::error!("unable to create demo log {}: {e}", path.display());
+                    return
+                }
+            };
+            let mut lines = generator.header();
+            for _ in 0..DEMO_DIVES_PER_LOG {
+                lines.extend(generator.dive());
+                for line in lines.drain(..) {
+                    if let Err(e) = writeln!(file, "{line}") {
+                        log::error!("error writing demo log: {e}");
+                        return
+                    }
+                    std::thread::sleep(Duration::from_millis(50));
+                }
+            }
+        }
+    });
+}
+
 fn main() {
     dotenvy::dotenv().ok();
     env_logger::init();
@@ -43,14 +134,132 @@ fn main() {
         .arg(arg!(-f --file <FILE> "File to parse and watch for updates")
             .required(false)
         )
+        .arg(arg!(-m --merge <FILE> "Merge logs from several members of the same party into a single json string. Give once per log, the first is used as the reference")
+            .required(false)
+            .action(clap::ArgAction::Append)
+        )
+        .arg(arg!(-d --diagnose <FILE> "Parse a single log and print a report of lines the parser did not recognize")
+            .required(false)
+        )
+        .arg(arg!(--sanitize <FILE> "Anonymize player names, character ids, party ids and user paths in a log so it can be shared, and print it")
+            .required(false)
+        )
+        .arg(arg!(--"drop-unrecognized" "With --sanitize, also drop any lines the parser does not recognize")
+            .required(false)
+            .action(clap::ArgAction::SetTrue)
+        )
+        .arg(arg!(--generate "Print a synthetic log, see --seed, --dives, --combats, --party, --skills, --crit-rate, --status-effects, --status-rate and --orb-rate")
+            .required(false)
+            .action(clap::ArgAction::SetTrue)
+        )
+        .arg(arg!(--demo "Show the overlay for a synthetic log that is continuously written to a temporary file")
+            .required(false)
+            .action(clap::ArgAction::SetTrue)
+        )
+        .arg(arg!(--seed <SEED> "Seed for --generate and --demo, the same seed always gives the same log")
+            .required(false)
+            .value_parser(clap::value_parser!(u64))
+        )
+        .arg(arg!(--dives <N> "Number of dives for --generate")
+            .required(false)
+            .value_parser(clap::value_parser!(usize))
+        )
+        .arg(arg!(--combats <N> "Number of combats per dive for --generate and --demo")
+            .required(false)
+            .value_parser(clap::value_parser!(usize))
+        )
+        .arg(arg!(--party <ASPECTS> "Comma separated aspects of the party for --generate and --demo, by id, abbreviation or name. e.g. MGM,WVR,STC")
+            .required(false)
+        )
+        .arg(arg!(--skills <SKILLS> "Comma separated skill names used by every player for --generate and --demo. e.g. Flurry,Barrage")
+            .required(false)
+        )
+        .arg(arg!(--"crit-rate" <RATE> "Chance of each hit being a critical hit for --generate and --demo, 0.0 - 1.0")
+            .required(false)
+            .value_parser(parse_rate)
+        )
+        .arg(arg!(--"status-effects" <EFFECTS> "Comma separated status effects applied on hits for --generate and --demo. e.g. Burn,Poison")
+            .required(false)
+        )
+        .arg(arg!(--"status-rate" <RATE> "Chance of each hit applying a status effect for --generate and --demo, 0.0 - 1.0")
+            .required(false)
+            .value_parser(parse_rate)
+        )
+        .arg(arg!(--"orb-rate" <RATE> "Chance of each player picking up an orb each turn for --generate and --demo, 0.0 - 1.0")
+            .required(false)
+            .value_parser(parse_rate)
+        )
+        .arg(arg!(--diagnostics "Collect parser diagnostics while watching, viewable from the overlay settings")
+            .required(false)
+            .action(clap::ArgAction::SetTrue)
+        )
+        .arg(arg!(--replay <FILE> "Replay a finished log in the overlay with its original timing, see --speed")
+            .required(false)
+        )
+        .arg(arg!(--speed <X> "Speed multiplier for --replay, e.g. 4 to replay four times as fast")
+            .required(false)
+            .requires("replay")
+            .value_parser(clap::value_parser!(f64))
+            .default_value("1")
+        )
+        .arg(arg!(--stdin "Read the log from stdin instead of a file, e.g. piped from another machine")
+            .required(false)
+            .action(clap::ArgAction::SetTrue)
+        )
+        .arg(arg!(--connect <ADDR> "Read the log from `forward` running on another machine instead of a file, e.g. 192.168.1.2:7878")
+            .required(false)
+        )
         .arg(arg!(-s --"skip-current" "Skip over parsing current log file")
             .required(false)
             .action(clap::ArgAction::SetTrue)
         )
+        .arg(arg!(--watcher <BACKEND> "How to notice the log changing. native also polls in case a notification is missed, and falls back to polling if the platform's file notifications fail. If not given, the setting last chosen in the overlay is used")
+            .required(false)
+            .value_parser(["native", "poll"])
+            .default_value("native")
+        )
+        .arg(arg!(--"poll-interval" <MS> "How often to check the log for changes when polling, or for missed notifications with the native watcher, in milliseconds. If not given, the setting last chosen in the overlay is used")
+            .required(false)
+            .value_parser(clap::value_parser!(u64).range(logreader::POLL_INTERVAL_RANGE))
+            .default_value("500")
+        )
+        .arg(arg!(--checkpoint [FILE] "Save progress reading the log every 30s, to resume from on the next start instead of parsing the whole log again. Saved to the temp directory if no FILE is given. Not resumed from with --skip-current")
+            .required(false)
+            .num_args(0..=1)
+        )
+        .arg(arg!(--"keep-dives" <N> "Only keep full stats for the N most recent dives, older dives only keep their totals")
+            .required(false)
+            .value_parser(clap::builder::RangedU64ValueParser::<usize>::new().range(1..))
+        )
+        .arg(arg!(--archive <FILE> "With --keep-dives, append the full stats of older dives to this file before dropping them")
+            .required(false)
+        )
+        .arg(arg!(--"reset-on-restart" "Clear all stats when the game restarts and recreates the log, instead of keeping them as an earlier session")
+            .required(false)
+            .action(clap::ArgAction::SetTrue)
+        )
         .arg(arg!(-w --windowed "Render in a window instead of as a borderless fullscreen overlay")
             .required(false)
             .action(clap::ArgAction::SetTrue)
         )
+        .arg(arg!(--patterns <FILE> "Load log line patterns from a file instead of the bundled ones. May be given multiple times for different game versions")
+            .required(false)
+            .action(clap::ArgAction::Append)
+        )
+        .arg(arg!(--aspects <FILE> "Load additional aspects from a file, adding to or overriding the bundled ones")
+            .required(false)
+            .action(clap::ArgAction::Append)
+        )
+        .subcommand(clap::Command::new("forward")
+            .about("Stream the log to the overlay on another machine, see --connect")
+            .arg(arg!(-f --file <FILE> "File to forward")
+                .required(false)
+            )
+            .arg(arg!(-l --listen <ADDR> "Address to listen on. Only this machine can connect by default, give e.g. 0.0.0.0:7878 to allow other machines. The log is sent unencrypted to anyone who connects, and includes character names and ids")
+                .required(false)
+                .default_value("127.0.0.1:7878")
+            )
+        )
     ;
 
     #[cfg(feature = "auto_update")]
@@ -83,22 +292,153 @@ fn main() {
         }
     }
 
+    // Forward mode
+    if let Some(forward) = matches.subcommand_matches("forward") {
+        let filepath = match forward.get_one::<String>("file") {
+            Some(filepath) => filepath.to_owned(),
+            None => default_logpath(),
+        };
+        let address = forward.get_one::<String>("listen").unwrap();
+        let listener = match std::net::TcpListener::bind(address) {
+            Ok(listener) => listener,
+            Err(e) => {
+                eprintln!("Unable to listen on {address}: {e}");
+                std::process::exit(1);
+            },
+        };
+        println!("Forwarding {filepath} on {address}");
+        if let Err(e) = Forwarder::new(filepath).serve(listener) {
+            eprintln!("Error forwarding: {e}");
+            std::process::exit(1);
+        }
+        return
+    }
+
+    for aspects in matches.get_many::<String>("aspects").unwrap_or_default() {
+        if let Err(e) = load_aspects(aspects) {
+            log::error!("error loading aspects: {e}");
+            std::process::exit(1);
+        }
+    }
+
+    let mut parser_options = LogParserOptions::default();
+    let mut sanitize_options = SanitizeOptions::default().drop_unrecognized(matches.get_flag("drop-unrecognized"));
+    for (index, patterns) in matches.get_many::<String>("patterns").unwrap_or_default().enumerate() {
+        match LinePatterns::from_file(patterns) {
+            Ok(patterns) => {
+                let patterns = Arc::new(patterns);
+                if index == 0 {
+                    sanitize_options = sanitize_options.patterns(patterns.clone());
+                }
+                parser_options = parser_options.patterns(patterns);
+            },
+            Err(e) => {
+                log::error!("error loading patterns: {e}");
+                std::process::exit(1);
+            }
+        }
+    }
+
     // Parse-only mode
     if let Some(file) = matches.get_one::<String>("parse") {
-        println!("{}", parse_log_to_json(file));
+        match parse_log_to_json(file, parser_options) {
+            Ok(output) => println!("{output}"),
+            Err(e) => {
+                eprintln!("Unable to read {file}: {e}");
+                std::process::exit(1);
+            },
+        }
         return
     }
 
-    let filepath = if let Some(filepath) = matches.get_one::<String>("file") {
-        filepath.to_owned()
-    } else {
-        default_logpath()
-    };
+    // Merge mode
+    if let Some(files) = matches.get_many::<String>("merge") {
+        let files: Vec<String> = files.cloned().collect();
+        match merge_logs_to_json(&files, parser_options) {
+            Ok(output) => println!("{output}"),
+            Err(e) => {
+                eprintln!("Unable to read {e}");
+                std::process::exit(1);
+            },
+        }
+        return
+    }
+
+    // Sanitize mode
+    if let Some(file) = matches.get_one::<String>("sanitize") {
+        match sanitize_log(file, sanitize_options) {
+            Ok(output) => print!("{output}"),
+            Err(e) => {
+                eprintln!("Unable to read {file}: {e}");
+                std::process::exit(1);
+            },
+        }
+        return
+    }
+
+    // Generate mode
+    if matches.get_flag("generate") {
+        print!("{}", LogGenerator::new(generator_options(&matches)).generate());
+        return
+    }
+
+    // Diagnostics-only mode
+    if let Some(file) = matches.get_one::<String>("diagnose") {
+        match diagnose_log(file, parser_options) {
+            Ok(output) => println!("{output}"),
+            Err(e) => {
+                eprintln!("Unable to read {file}: {e}");
+                std::process::exit(1);
+            },
+        }
+        return
+    }
+
+    let parser_options = parser_options.diagnostics(matches.get_flag("diagnostics"));
 
     let skip_current = matches.get_flag("skip-current");
-    let reader = LogReader::new(filepath.clone(), Duration::from_secs(2), skip_current);
+    let watcher = match matches.get_one::<String>("watcher").map(String::as_str) {
+        Some("poll") => WatcherBackend::Poll,
+        _ => WatcherBackend::Native,
+    };
+    let poll_interval = *matches.get_one::<u64>("poll-interval").unwrap();
+    let reader_options = LogReaderOptions::default()
+        .poll_duration(Duration::from_millis(poll_interval))
+        .parser_options(parser_options)
+        .reset_on_reopen(matches.get_flag("reset-on-restart"))
+        .keep_dives(matches.get_one::<usize>("keep-dives").copied())
+        .archive(matches.get_one::<String>("archive").map(Into::into));
+    let reader = if matches.get_flag("demo") {
+        let path = std::env::temp_dir().join("inkbound-dps-demo.log");
+        spawn_demo_writer(path.clone(), generator_options(&matches));
+        LogReader::new(FileSource::new(path.to_string_lossy()).watcher(watcher), reader_options)
+    } else if let Some(filepath) = matches.get_one::<String>("replay") {
+        match ReplaySource::open(filepath) {
+            Ok(source) => LogReader::new(source.speed(*matches.get_one::<f64>("speed").unwrap()), reader_options),
+            Err(e) => {
+                eprintln!("Unable to read {filepath}: {e}");
+                std::process::exit(1);
+            },
+        }
+    } else if matches.get_flag("stdin") {
+        LogReader::new(StreamSource::stdin(), reader_options)
+    } else if let Some(address) = matches.get_one::<String>("connect") {
+        LogReader::new(RemoteSource::new(address), reader_options)
+    } else {
+        let filepath = match matches.get_one::<String>("file") {
+            Some(filepath) => filepath.to_owned(),
+            None => default_logpath(),
+        };
+        // Only the game's log is checkpointed, the other sources are either short or can't resume
+        let checkpoint = match matches.get_one::<String>("checkpoint") {
+            Some(checkpoint) => Some(checkpoint.into()),
+            None if matches.contains_id("checkpoint") => Some(std::env::temp_dir().join("inkbound-dps-checkpoint.json")),
+            None => None,
+        };
+        LogReader::new(FileSource::new(filepath).skip_current(skip_current).watcher(watcher), reader_options.checkpoint(checkpoint))
+    };
 
-    log::info!("starting watch of file: {}", filepath);
+    log::info!("starting watch of {}", reader.source_description());
 
     #[cfg(not(debug_assertions))]
     let mode = if matches.get_flag("windowed") {
@@ -109,5 +449,11 @@ fn main() {
     // Just always use windowed mode in debug builds
     #[cfg(debug_assertions)]
     let mode = overlay::OverlayMode::WindowedOverlay;
-    overlay::spawn_overlay(reader, mode);
+    // Only what was actually given overrides the settings remembered by the overlay, not the defaults
+    let given = |id: &str| matches.value_source(id) == Some(clap::parser::ValueSource::CommandLine);
+    let reader_args = overlay::ReaderArgs {
+        poll_interval: given("poll-interval").then_some(poll_interval),
+        watcher: given("watcher").then_some(watcher),
+    };
+    overlay::spawn_overlay(reader, mode, reader_args);
 }
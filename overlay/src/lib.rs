@@ -13,21 +13,13 @@ trait DefaultColor {
     fn default_color(&self) -> egui::Color32;
 }
 
-// Out of scope for the parser, so plopping it here
+// Colors are out of scope for the parser, which only stores the rgb values in the aspect registry
 impl DefaultColor for Aspect {
     /// Get the default color for a given aspect
     fn default_color(&self) -> egui::Color32 {
-        use egui::Color32;
-        match self {
-            Aspect::MagmaMiner   => Color32::from_rgb(184, 67, 0),
-            Aspect::Mosscloak    => Color32::from_rgb(76, 142, 33),
-            Aspect::Clairvoyant  => Color32::from_rgb(194, 66, 66),
-            Aspect::Weaver       => Color32::from_rgb(151, 30, 167),
-            Aspect::Obelisk      => Color32::from_rgb(55, 147, 147),
-            Aspect::StarCaptain  => Color32::from_rgb(188, 150, 53),
-            Aspect::Chainbreaker => Color32::from_rgb(137, 26, 37),
-            Aspect::Godkeeper    => Color32::from_rgb(213, 123, 22),
-            Aspect::Unknown(_)   => Color32::DARK_GRAY,
+        match self.default_rgb() {
+            Some((r, g, b)) => egui::Color32::from_rgb(r, g, b),
+            None => egui::Color32::DARK_GRAY,
         }
     }
 }
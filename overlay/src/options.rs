@@ -1,4 +1,5 @@
 use inkbound_parser::aspects::Aspect;
+use logreader::WatcherBackend;
 use serde::{Serialize, Deserialize};
 
 use crate::DefaultColor;
@@ -11,6 +12,9 @@ pub struct OverlayOptions {
     pub crit_bar_opacity: u8,
     pub colors: ColorOptions,
     pub auto_check_update: bool,
+    /// Set from the settings window, used instead of the command line's on the next start
+    pub poll_interval: Option<u64>,
+    pub watcher: Option<WatcherBackend>,
 }
 
 // TODO: consider using a crate to make this whole impl not necessary
@@ -22,6 +26,8 @@ impl Default for OverlayOptions {
             crit_bar_opacity: 128,
             colors: ColorOptions::default(),
             auto_check_update: false,
+            poll_interval: None,
+            watcher: None,
         }
     }
 }
@@ -5,7 +5,7 @@ use serde::de::DeserializeOwned;
 
 use crate::{windows::{self, WindowDisplay, WindowId, OverlayWindow}, options::OverlayOptions};
 
-use logreader::LogReader;
+use logreader::{LogReader, WatcherBackend};
 
 static OPTIONS_STORAGE_KEY: &str = "overlayoptions";
 static WINDOWS_STORAGE_KEY: &str = "overlaywindows";
@@ -19,6 +19,7 @@ type EnabledWindowConfig = BTreeSet<WindowId>;
 pub struct WindowState {
     pub settings: windows::SettingsState,
     pub color_settings: windows::ColorSettingsState,
+    pub diagnostics: windows::DiagnosticsState,
     #[cfg(feature = "auto_update")]
     pub update: crate::updater::UpdateState,
 }
@@ -69,11 +70,15 @@ fn load_from_storage<T: Default + DeserializeOwned>(storage: Option<&dyn eframe:
 }
 
 impl Overlay {
-    pub fn new(_cc: &eframe::CreationContext<'_>, logreader: LogReader, overlay_mode: OverlayMode) -> Self {
+    pub fn new(_cc: &eframe::CreationContext<'_>, mut logreader: LogReader, overlay_mode: OverlayMode, reader_args: ReaderArgs) -> Self {
         _cc.egui_ctx.set_visuals(egui::style::Visuals {
             ..Default::default()
         });
 
+        // Only repaint for new data when there is some, rather than polling for it
+        let ctx = _cc.egui_ctx.clone();
+        logreader.on_change(move || ctx.request_repaint());
+
         let options = load_from_storage(_cc.storage, OPTIONS_STORAGE_KEY);
 
         // If options could not be loaded, either there is an error, or this is the first run.
@@ -89,6 +94,14 @@ impl Overlay {
 
         let options = options.unwrap_or_default();
 
+        // Reading settings changed from the settings window last time, unless given on the command line this time
+        if let (Some(poll_interval), None) = (options.poll_interval, reader_args.poll_interval) {
+            logreader.set_poll_duration(std::time::Duration::from_millis(poll_interval));
+        }
+        if let (Some(watcher), None) = (options.watcher, reader_args.watcher) {
+            logreader.set_watcher(watcher);
+        }
+
         let mut window_state = WindowState::default();
         window_state.color_settings.sync_from_options(&options);
 
@@ -199,11 +212,7 @@ impl eframe::App for Overlay {
 
 /// Main entrypoint to draw all the egui widgets and things
 pub fn draw_overlay(overlay: &mut Overlay, ctx: &egui::Context) {
-    let datalog = {
-        // TODO: This seems very wasteful of memory, consider doing some kind of cache
-        //  update clone only if data changed, etc
-        overlay.logreader.get_datalog().read().unwrap().clone()
-    };
+    let datalog = overlay.logreader.snapshot();
 
     windows::draw_settings_window(overlay, ctx);
 
@@ -250,8 +259,16 @@ pub enum OverlayMode {
     // TODO: Actual windowed mode?
 }
 
+/// Reading settings given on the command line, which take precedence over the ones remembered from the settings window
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ReaderArgs {
+    /// In milliseconds
+    pub poll_interval: Option<u64>,
+    pub watcher: Option<WatcherBackend>,
+}
+
 /// Entrypoint for the main application to spawn the actual overlay window and such
-pub fn spawn_overlay(logreader: LogReader, mode: OverlayMode) {
+pub fn spawn_overlay(logreader: LogReader, mode: OverlayMode, reader_args: ReaderArgs) {
     let viewport = match mode {
         OverlayMode::Overlay =>
             ViewportBuilder::default()
@@ -271,7 +288,7 @@ pub fn spawn_overlay(logreader: LogReader, mode: OverlayMode) {
         ..Default::default()
     };
 
-    eframe::run_native("Inkbound Overlay", native_options, Box::new(|c| Box::new(Overlay::new(c, logreader, mode)))).unwrap();
+    eframe::run_native("Inkbound Overlay", native_options, Box::new(|c| Box::new(Overlay::new(c, logreader, mode, reader_args)))).unwrap();
 }
 
 
@@ -0,0 +1,49 @@
+use egui::{Window, RichText};
+
+use crate::Overlay;
+
+#[derive(Default)]
+pub struct DiagnosticsState {
+    pub show: bool,
+}
+
+pub fn draw_diagnostics_window(overlay: &mut Overlay, ctx: &egui::Context) {
+    let diagnostics = overlay.logreader.get_diagnostics();
+
+    Window::new("Parser Diagnostics")
+        .open(&mut overlay.window_state.diagnostics.show)
+        .show(ctx, |ui| {
+            let diagnostics = if let Some(diagnostics) = diagnostics {
+                diagnostics
+            } else {
+                ui.label("Diagnostics are disabled, start with --diagnostics to enable them.");
+                return
+            };
+
+            ui.label(format!("Lines: {}, matched: {}, ignored: {}",
+                diagnostics.lines, diagnostics.matched, diagnostics.lines - diagnostics.matched));
+
+            ui.collapsing(format!("Partial matches ({})", diagnostics.partial.len()), |ui| {
+                if diagnostics.partial.is_empty() {
+                    ui.label("None");
+                }
+                for (pattern, partial) in diagnostics.partial.iter() {
+                    ui.label(RichText::new(format!("{pattern}: {}", partial.count)).strong())
+                        .on_hover_text(partial.example.as_str());
+                }
+            }).header_response.on_hover_text("Lines that contained a pattern's keyword, but failed the full pattern.\nThese likely mean a pattern is broken. Hover for an example line.");
+
+            ui.collapsing(format!("Ignored lines ({})", diagnostics.ignored.len()), |ui| {
+                egui::ScrollArea::vertical().max_height(400.0).show(ui, |ui| {
+                    egui::Grid::new("Ignored Lines").striped(true).show(ui, |ui| {
+                        for (prefix, count) in diagnostics.ignored_by_count() {
+                            ui.label(count.to_string());
+                            ui.label(prefix.as_str());
+                            ui.end_row();
+                        }
+                    });
+                });
+            });
+        }
+    );
+}
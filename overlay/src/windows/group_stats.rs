@@ -7,7 +7,7 @@ use serde::{Deserialize, Serialize};
 use interpolator::*;
 use derivative::Derivative;
 
-use super::{extractors::{StatSelection, StatSelectionState}, FormatSelection, AspectAbbv, div_or_zero};
+use super::{extractors::{StatSelection, StatSelectionState}, FormatSelection, div_or_zero};
 
 use crate::OverlayOptions;
 
@@ -42,7 +42,7 @@ impl WindowDisplay for GroupStatsWindow {
         });
 
         if let Some(stats) = self.get_current_player_stat_list(data) {
-            self.draw_group_stats_plot(ui, options, stats.values().collect());
+            self.draw_group_stats_plot(ui, options, stats.iter().collect());
         } else {
             ui.label(super::NO_DATA_MSG.to_string());
         };
@@ -6,7 +6,7 @@ use strum::{IntoEnumIterator, EnumIter};
 
 use crate::{options::ColorOptions, OverlayOptions};
 
-use super::{show_dive_selection_box, WindowDisplay, extractors::{StatSelectionState, StatSelection}};
+use super::{show_dive_selection_box, DiveFilter, WindowDisplay, extractors::{StatSelectionState, StatSelection}};
 
 #[derive(Default, PartialEq, Serialize, Deserialize, Debug, EnumIter, Clone, Copy)]
 pub enum HistoryMode {
@@ -30,6 +30,7 @@ impl std::fmt::Display for HistoryMode {
 #[derive(Default, Debug)]
 pub struct HistoryState {
     pub dive: usize,
+    pub filter: DiveFilter,
 }
 
 #[derive(Default, Debug, Serialize, Deserialize, EnumIter, PartialEq, Eq, Clone, Copy)]
@@ -111,7 +112,7 @@ impl HistoryWindow {
 
     pub fn draw_history_window(&mut self, ui: &mut egui::Ui, options: &OverlayOptions, datalog: &DataLog) {
         ui.collapsing("⛭", |ui| {
-            show_dive_selection_box(ui, &mut self.state.dive, datalog.dives.len());
+            show_dive_selection_box(ui, &mut self.state.dive, &mut self.state.filter, datalog);
 
             self.show_stat_selection_box(ui);
 
@@ -149,6 +150,7 @@ impl HistoryWindow {
 
         ui.separator();
 
+        self.state.filter.clamp(datalog, &mut self.state.dive);
         let dive = if let Some(dive) = datalog.dives.get(self.state.dive) {
             dive
         } else {
@@ -200,11 +202,11 @@ impl HistoryWindow {
     fn generate_split_bars(&self, dive: &DiveLog, colors: &ColorOptions) -> Vec<Bar> {
         let bar_group_width = self.options.group_bar_width;
         dive.combats.iter().rev().enumerate().flat_map(|(combat_index, combat)| {
-            let players: Vec<PlayerStats> = combat.player_stats.player_stats.values().cloned().collect();
+            let players: Vec<PlayerStats> = combat.player_stats.iter().cloned().collect();
             let players = self.sort_players(players, self.options.bar_order);
             players.iter().enumerate().map(|(pind, p)| {
                 let pind = pind as f64;
-                let num_players = combat.player_stats.player_stats.len() as f64;
+                let num_players = combat.player_stats.len() as f64;
                 let bar_width = bar_group_width / num_players;
                 // let x_offset = ((pind + bar_group_width / 2.0) * width) - (bar_group_width / 2.0);
                 let x_offset = pind * bar_width - ((bar_group_width - bar_width) / 2.0);
@@ -219,7 +221,7 @@ impl HistoryWindow {
     #[inline]
     fn generate_stacked_bars(&self, dive: &DiveLog, colors: &ColorOptions, percent: bool) -> (Vec<Bar>, Option<Vec<Text>>) {
         let bars = dive.combats.iter().rev().enumerate().flat_map(|(combat_index, combat)| {
-            let players: Vec<PlayerStats> = combat.player_stats.player_stats.values().cloned().collect();
+            let players: Vec<PlayerStats> = combat.player_stats.iter().cloned().collect();
             // Skip sum calculation if not in percent mode, save a bit of effort
             let total: f64 = if percent { players.iter().map(|e| self.extract_stat(e)).sum() } else { 0.0 };
             let players = self.sort_players(players, self.options.bar_order);
@@ -247,7 +249,7 @@ impl HistoryWindow {
         // TODO: This totally can be done in one pass with the previous
         let texts = if self.options.stacked_show_totals {
             Some(dive.combats.iter().rev().enumerate().map(|(combat_index, combat)| {
-                let stat = combat.player_stats.player_stats.values().fold(0.0, |acc, elem| acc + self.extract_stat(elem));
+                let stat = combat.player_stats.iter().fold(0.0, |acc, elem| acc + self.extract_stat(elem));
                 Text::new(
                     PlotPoint { x: combat_index as f64 + 1.0, y: if percent { 100.0 } else { stat } },
                     format!("{}", stat)
@@ -1,7 +1,5 @@
 mod settings;
 
-use std::collections::HashMap;
-
 pub use settings::*;
 
 mod skill_totals;
@@ -16,10 +14,13 @@ pub use history::*;
 mod stat_table;
 pub use stat_table::*;
 
+mod diagnostics;
+pub use diagnostics::*;
+
 pub mod extractors;
 
 use serde::{Deserialize, Serialize};
-use inkbound_parser::parser::{DataLog, PlayerStats};
+use inkbound_parser::parser::{DataLog, DiveLog, PlayerStatList};
 
 use crate::OverlayOptions;
 
@@ -90,13 +91,74 @@ impl WindowDisplay for OverlayWindow {
     }
 }
 
+/// Optional filters for which dives are listed in the dive selector
+#[derive(Debug, Default)]
+pub struct DiveFilter {
+    pub session: Option<usize>,
+    pub character: Option<String>,
+}
+
+impl DiveFilter {
+    fn matches(&self, data: &DataLog, dive: &DiveLog) -> bool {
+        self.session.is_none_or(|session| session == dive.session)
+            && self.character.as_ref().is_none_or(|character|
+                data.session_of(dive).and_then(|s| s.character.as_ref()) == Some(character)
+            )
+    }
+
+    /// Move the selected dive to the most recent one the filter shows, if it hides the current selection
+    pub fn clamp(&self, data: &DataLog, dive: &mut usize) {
+        if data.dives.get(*dive).is_some_and(|d| self.matches(data, d)) {
+            return
+        }
+        if let Some(visible) = data.dives.iter().position(|d| self.matches(data, d)) {
+            *dive = visible;
+        }
+    }
+}
+
+fn session_label(data: &DataLog, session: usize) -> String {
+    let character = data.sessions.get(session).and_then(|s| s.character.as_deref()).unwrap_or("Unknown");
+    format!("{} - {character}", session + 1)
+}
+
 // TODO: Remove this eventually, probably when options are derived almost entirely from traits
-pub fn show_dive_selection_box(ui: &mut egui::Ui, dive_state: &mut usize, num_dives: usize) {
+pub fn show_dive_selection_box(ui: &mut egui::Ui, dive_state: &mut usize, filter: &mut DiveFilter, data: &DataLog) {
+    let num_dives = data.dives.len();
+
+    // Only worth filtering if there's more than one session to pick from
+    if data.sessions.len() > 1 {
+        egui::ComboBox::from_label("Session")
+            .selected_text(filter.session.map(|s| session_label(data, s)).unwrap_or("All".to_string()))
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut filter.session, None, "All");
+                for session in (0..data.sessions.len()).rev() {
+                    ui.selectable_value(&mut filter.session, Some(session), session_label(data, session));
+                }
+            });
+
+        let characters = data.characters();
+        if characters.len() > 1 {
+            egui::ComboBox::from_label("Character")
+                .selected_text(filter.character.clone().unwrap_or("All".to_string()))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut filter.character, None, "All");
+                    for character in characters {
+                        ui.selectable_value(&mut filter.character, Some(character.to_string()), character);
+                    }
+                });
+        }
+    }
+
+    filter.clamp(data, dive_state);
     egui::ComboBox::from_label("Select Dive")
         .selected_text(inverted_number_label(*dive_state, num_dives))
         .show_ui(ui, |ui| {
-            for dive in 0..num_dives {
-                ui.selectable_value(dive_state, dive, inverted_number_label(dive, num_dives));
+            for (dive, log) in data.dives.iter().enumerate().filter(|(_, d)| filter.matches(data, d)) {
+                let label = inverted_number_label(dive, num_dives);
+                // Older dives may only have their totals left
+                let label = if log.compacted { format!("{label} (totals only)") } else { label };
+                ui.selectable_value(dive_state, dive, label);
             }
         });
 }
@@ -123,6 +185,7 @@ impl std::fmt::Display for DiveCombatSelection {
 pub struct DiveCombatSelectionState {
     pub dive: usize,
     pub combat: usize,
+    pub filter: DiveFilter,
 }
 
 // TODO: consider making this two seperate traits, and create combiner auto-impl'd traits to define the fancier stuff
@@ -149,16 +212,8 @@ pub trait DiveCombatSplit: WindowDisplay + Default + 'static {
     }
 
     fn show_dive_selection_box(&mut self, ui: &mut egui::Ui, data: &DataLog) {
-        let dive_state = &mut self.state().dive;
-        let num_dives = data.dives.len();
-
-        egui::ComboBox::from_label("Select Dive")
-            .selected_text(inverted_number_label(*dive_state, num_dives))
-            .show_ui(ui, |ui| {
-                for dive in 0..num_dives {
-                    ui.selectable_value(dive_state, dive, inverted_number_label(dive, num_dives));
-                }
-            });
+        let state = self.state();
+        show_dive_selection_box(ui, &mut state.dive, &mut state.filter, data);
     }
 
     fn show_combat_selection_box(&mut self, ui: &mut egui::Ui, data: &DataLog) {
@@ -189,13 +244,16 @@ pub trait DiveCombatSplit: WindowDisplay + Default + 'static {
         }
     }
 
-    fn get_current_player_stat_list<'a>(&mut self, data: &'a DataLog) -> Option<&'a HashMap<String, PlayerStats>> {
+    fn get_current_player_stat_list<'a>(&mut self, data: &'a DataLog) -> Option<&'a PlayerStatList> {
+        // New dives, or a changed filter, may have moved the selection onto a hidden dive
+        let state = self.state();
+        state.filter.clamp(data, &mut state.dive);
         match self.mode() {
-            DiveCombatSelection::Dive => data.dives.get(self.state().dive).map(|d| &d.player_stats.player_stats),
+            DiveCombatSelection::Dive => data.dives.get(self.state().dive).map(|d| &d.player_stats),
             DiveCombatSelection::Combat => {
                 let state = self.state();
                 if let Some(dive) = data.dives.get(state.dive) {
-                    dive.combats.get(state.combat).map(|c| &c.player_stats.player_stats)
+                    dive.combats.get(state.combat).map(|c| &c.player_stats)
                 } else {
                     None
                 }
@@ -208,15 +266,17 @@ pub trait PlayerSelection {
     /// Get a mutable reference to how the window is storing the player state
     fn player(&mut self) -> &mut Option<String>;
 
-    fn show_player_selection_box(&mut self, ui: &mut egui::Ui, player_stats: &HashMap<String, PlayerStats>) {
+    fn show_player_selection_box(&mut self, ui: &mut egui::Ui, player_stats: &PlayerStatList) {
         let player = self.player();
         egui::ComboBox::from_label("Select Player")
                     .selected_text(player.as_ref().unwrap_or(&"".to_string()).to_string())
                     .show_ui(ui, |ui| {
                         // Assumes None -> pov character. Probably could be improved, especially if POV detection fails
                         ui.selectable_value(player, None, "YOU");
-                        for pkey in player_stats.keys() {
-                            ui.selectable_value(player, Some(pkey.clone()), pkey);
+                        // Selection is stored by PlayerKey, so players with the same name stay distinct
+                        for stats in player_stats.iter() {
+                            let data = &stats.player_data;
+                            ui.selectable_value(player, Some(data.key.to_string()), format!("{} ({})", data.name, data.class.abbv()));
                         }
                     }
                 );
@@ -269,30 +329,6 @@ pub trait FormatSelection {
     }
 }
 
-// TODO: Consider just putting this in parser Aspect?
-pub trait AspectAbbv {
-    fn abbv(&self) -> String;
-}
-
-use inkbound_parser::aspects::Aspect;
-impl AspectAbbv for Aspect {
-    /// Get an abbreviated name for the aspect.
-    // TODO: consider allowing users to define these?
-    fn abbv(&self) -> String {
-        match self {
-            Aspect::MagmaMiner => "MGM",
-            Aspect::Mosscloak => "MSC",
-            Aspect::Weaver => "WVR",
-            Aspect::Obelisk => "OBE",
-            Aspect::Clairvoyant => "CLV",
-            Aspect::StarCaptain => "STC",
-            Aspect::Chainbreaker => "CHB",
-            Aspect::Godkeeper => "GKR",
-            Aspect::Unknown(_) => "UNK",
-        }.to_string()
-    }
-}
-
 static NO_DATA_MSG: &str = "Waiting for data...";
 
 pub static ALLOWED_STATUS_EFFECTS: [&str; 5] = [
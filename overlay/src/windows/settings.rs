@@ -1,7 +1,8 @@
 use std::collections::BTreeMap;
 
 use egui::{Window, Color32};
-use inkbound_parser::aspects::Aspect;
+use inkbound_parser::aspects::{self, Aspect, AspectInfo};
+use logreader::WatcherBackend;
 use strum::{IntoEnumIterator, EnumIter};
 
 use crate::{Overlay, DefaultColor};
@@ -30,6 +31,10 @@ impl HighlightWindow {
 pub struct SettingsState {
     add_window: Option<AddWindowChoice>,
     pub highlight_window: HighlightWindow,
+    /// Poll interval being dragged to, in ms, applied once released
+    poll_interval: Option<u64>,
+    /// Replay position being dragged to, in seconds, applied once released
+    seek: Option<f64>,
 }
 
 #[derive(EnumIter, Debug, PartialEq, Eq)]
@@ -142,6 +147,41 @@ pub fn draw_settings_window(overlay: &mut Overlay, ctx: &egui::Context) {
                     .on_hover_text("Set the opacity of the overlain crit bar.");
             }
             ui.checkbox(&mut overlay.window_state.color_settings.show, "Show Color Editor");
+            ui.checkbox(&mut overlay.window_state.diagnostics.show, "Show Parser Diagnostics")
+                .on_hover_text("Show which log lines the parser did not recognize.\n\nUseful for finding broken patterns after a game update.");
+
+            let mut poll_interval = overlay.window_state.settings.poll_interval
+                .unwrap_or(overlay.logreader.poll_duration().as_millis() as u64);
+            let poll_slider = ui.add(egui::Slider::new(&mut poll_interval, logreader::POLL_INTERVAL_RANGE).logarithmic(true).suffix(" ms").text("Poll interval"))
+                .on_hover_text("How often to check the log for changes. With native watching, this only catches changes the system didn't report.\n\nChanging this restarts reading without losing any stats.");
+            // Only restart once the new value is settled on, not on every step of a drag
+            if poll_slider.dragged() {
+                overlay.window_state.settings.poll_interval = Some(poll_interval);
+            } else if poll_slider.changed() || overlay.window_state.settings.poll_interval.is_some() {
+                overlay.window_state.settings.poll_interval = None;
+                overlay.options.poll_interval = Some(poll_interval);
+                overlay.logreader.set_poll_duration(std::time::Duration::from_millis(poll_interval));
+            }
+
+            if let Some(mut watcher) = overlay.logreader.watcher() {
+                egui::ComboBox::from_label("Watch for changes")
+                    .selected_text(watcher_label(watcher))
+                    .show_ui(ui, |ui| {
+                        for backend in [WatcherBackend::Native, WatcherBackend::Poll] {
+                            ui.selectable_value(&mut watcher, backend, watcher_label(backend));
+                        }
+                    }).response
+                    .on_hover_text("How to notice the log changing.\n\nNative uses the system's file notifications, and still polls in case any are missed. Try polling if the stats stop updating.");
+                if Some(watcher) != overlay.logreader.watcher() {
+                    overlay.options.watcher = Some(watcher);
+                    overlay.logreader.set_watcher(watcher);
+                }
+            }
+
+            if let Some(replay) = overlay.logreader.replay() {
+                ui.separator();
+                draw_replay_controls(ui, replay, &mut overlay.window_state.settings.seek);
+            }
 
             #[cfg(feature = "auto_update")]
             {
@@ -164,18 +204,149 @@ pub fn draw_settings_window(overlay: &mut Overlay, ctx: &egui::Context) {
                         ui.label(format!("{status}"));
                         ui.spinner()
                     },
+                    logreader::LogReaderStatus::WaitingForFile => {
+                        ui.label(format!("{status}"))
+                            .on_hover_text(format!("{}\n\nReading will start once the game creates it.", overlay.logreader.source_description()));
+                        ui.spinner()
+                    },
                     logreader::LogReaderStatus::Errored => ui.colored_label(egui::Rgba::RED, format!("{status}")),
                     _ => ui.label(format!("{status}")),
                 };
             });
+            if let Some(error) = overlay.logreader.get_error() {
+                ui.colored_label(egui::Color32::RED, error);
+            }
+            if let Some(warning) = overlay.logreader.get_warning() {
+                ui.colored_label(egui::Color32::YELLOW, format!("⚠ {warning}"));
+            }
+            ui.collapsing("Reader Diagnostics", |ui| draw_reader_metrics(ui, &overlay.logreader.metrics()));
 
             if overlay.window_state.color_settings.show {
                 draw_color_settings_window(overlay, ctx);
             }
+            if overlay.window_state.diagnostics.show {
+                super::draw_diagnostics_window(overlay, ctx);
+            }
         }
     );
 }
 
+/// Format seconds as e.g. "1:02:03" or "2:03"
+fn format_time(seconds: f64) -> String {
+    let seconds = seconds as u64;
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    match hours {
+        0 => format!("{minutes}:{seconds:02}"),
+        _ => format!("{hours}:{minutes:02}:{seconds:02}"),
+    }
+}
+
+/// Format a byte count as e.g. "512 B" or "1.5 MiB"
+fn format_bytes(bytes: u64) -> String {
+    let mut size = bytes as f64;
+    for unit in ["B", "KiB", "MiB"] {
+        if size < 1024. {
+            return match unit {
+                "B" => format!("{bytes} B"),
+                _ => format!("{size:.1} {unit}"),
+            }
+        }
+        size /= 1024.;
+    }
+    format!("{size:.1} GiB")
+}
+
+fn draw_reader_metrics(ui: &mut egui::Ui, metrics: &logreader::ReaderMetrics) {
+    egui::Grid::new("Reader Metrics").num_columns(2).show(ui, |ui| {
+        ui.label("Read");
+        ui.label(format!("{} lines, {}", metrics.lines, format_bytes(metrics.bytes)));
+        ui.end_row();
+
+        ui.label("Throughput");
+        ui.label(format!("{:.0} lines/s", metrics.lines_per_second()))
+            .on_hover_text("Lines read per second of time spent reading and parsing.");
+        ui.end_row();
+
+        ui.label("Behind by");
+        match metrics.lag() {
+            Some(0) => ui.label("Caught up"),
+            Some(lag) => ui.colored_label(egui::Color32::YELLOW, format_bytes(lag)),
+            None => ui.label("Unknown"),
+        }.on_hover_text("How much of the log is still to be read.");
+        ui.end_row();
+
+        ui.label("Last checked");
+        match metrics.offset_checked.and_then(|time| time.elapsed().ok()) {
+            Some(elapsed) => ui.label(format!("{} ago", format_time(elapsed.as_secs_f64()))),
+            None => ui.label("Unknown"),
+        }.on_hover_text("When the log was last read up to its end.\n\nIf this keeps growing while the log is behind, changes to the log aren't being noticed.");
+        ui.end_row();
+
+        ui.label("Last event");
+        match metrics.last_event.and_then(|time| time.elapsed().ok()) {
+            Some(elapsed) => ui.label(format!("{} ago", format_time(elapsed.as_secs_f64()))),
+            None => ui.label("None yet"),
+        };
+        ui.end_row();
+
+        ui.label("Parse errors");
+        ui.label(metrics.parse_errors.to_string())
+            .on_hover_text("Lines that looked like a known line but couldn't be parsed.\n\nSee the parser diagnostics for details.");
+        ui.end_row();
+
+        ui.label("Read errors");
+        ui.label(metrics.read_errors.to_string());
+        ui.end_row();
+    });
+    ui.collapsing("Events", |ui| {
+        egui::Grid::new("Reader Events").num_columns(2).show(ui, |ui| {
+            for (kind, count) in metrics.events.iter() {
+                ui.label(*kind);
+                ui.label(count.to_string());
+                ui.end_row();
+            }
+        });
+    });
+    // Keep the time since the last event ticking over while the section is open
+    ui.ctx().request_repaint_after(std::time::Duration::from_secs(1));
+}
+
+fn watcher_label(backend: WatcherBackend) -> &'static str {
+    match backend {
+        WatcherBackend::Native => "Native",
+        WatcherBackend::Poll => "Poll",
+    }
+}
+
+fn draw_replay_controls(ui: &mut egui::Ui, replay: &logreader::ReplayControl, pending_seek: &mut Option<f64>) {
+    ui.horizontal(|ui| {
+        let paused = replay.is_paused();
+        if ui.button(if paused { "▶" } else { "⏸" }).on_hover_text(if paused { "Play" } else { "Pause" }).clicked() {
+            replay.set_paused(!paused);
+        }
+        let mut speed = replay.speed();
+        if ui.add(egui::Slider::new(&mut speed, 0.25..=64.0).logarithmic(true).suffix("×").text("Replay speed")).changed() {
+            replay.set_speed(speed);
+        }
+    });
+    let duration = replay.duration().as_secs_f64();
+    let mut position = pending_seek.unwrap_or(replay.position().as_secs_f64());
+    let seek = egui::Slider::new(&mut position, 0.0..=duration)
+        .custom_formatter(|seconds, _| format_time(seconds))
+        .text(format!("/ {}", format_time(duration)));
+    let seek = ui.add(seek).on_hover_text("Seek through the replay.\n\nSeeking backwards reads the log again from the start.");
+    // Seeking backwards reparses the whole log, so only seek once released, not on every step of a drag
+    if seek.dragged() {
+        *pending_seek = Some(position);
+    } else if seek.changed() || pending_seek.is_some() {
+        *pending_seek = None;
+        replay.seek(std::time::Duration::from_secs_f64(position));
+    }
+    // Keep the position moving through quiet stretches of the log, which don't change the datalog
+    if !replay.is_paused() {
+        ui.ctx().request_repaint_after(std::time::Duration::from_millis(250));
+    }
+}
 
 pub struct ColorSettingsState {
     pub show: bool,
@@ -184,10 +355,14 @@ pub struct ColorSettingsState {
 
 impl Default for ColorSettingsState {
     fn default() -> Self {
-        let aspects: BTreeMap<Aspect, Color32> = Aspect::iter().map(|elem| {
-            let color = elem.default_color();
-            (elem, color)
-        }).collect();
+        // Collect first, default_color also reads the registry
+        let known: Vec<Aspect> = aspects::registry().iter().map(AspectInfo::aspect).collect();
+        let aspects: BTreeMap<Aspect, Color32> = known.into_iter()
+            .chain(std::iter::once(Aspect::Unknown("".to_string())))
+            .map(|elem| {
+                let color = elem.default_color();
+                (elem, color)
+            }).collect();
 
         Self {
             show: false,
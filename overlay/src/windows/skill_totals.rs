@@ -3,7 +3,7 @@ use std::collections::HashMap;
 use derivative::Derivative;
 use egui::Ui;
 use egui_plot::{Plot, BarChart, Bar, Text, PlotPoint};
-use inkbound_parser::parser::{PlayerStats, DataLog};
+use inkbound_parser::parser::{PlayerStats, PlayerKey, DataLog};
 use interpolator::Formattable;
 use serde::{Deserialize, Serialize};
 
@@ -78,9 +78,13 @@ impl WindowDisplay for SkillTotalsWindow {
         };
 
         let player_stats = if let Some(selection) = self.player.as_ref() {
-            player_stats.get(selection)
-        } else if let Some(pov) = data.pov.as_ref() {
-            player_stats.get(pov)
+            // Keys include the player's handle in the dive they were picked from, so other dives fall back to the name.
+            //  Older configs stored the name instead of the key
+            let name = selection.rsplit_once(" (").map_or(selection.as_str(), |(name, _)| name);
+            player_stats.get(&PlayerKey(selection.clone())).or_else(|| player_stats.get_by_name(name))
+        } else if let Some(pov) = data.dives.get(self.state.dive).and_then(|dive| data.pov_of(dive)) {
+            // The character played in the selected dive, which may not be the current one
+            player_stats.get_by_name(pov)
         } else {
             None
         };
@@ -62,9 +62,9 @@ impl WindowDisplay for StatTableWindow {
             .columns(Column::auto().resizable(true), player_stats.len() + 1)
             .header(15.0, |mut header| {
                 header.col(|_ui| {}); // Empty column to ensure alignment
-                for player in player_stats.keys() {
+                for player in player_stats.iter() {
                     header.col(|ui| {
-                        ui.label(RichText::new(player).strong());
+                        ui.label(RichText::new(&player.player_data.name).strong());
                     });
                 }
             })
@@ -73,7 +73,7 @@ impl WindowDisplay for StatTableWindow {
                     row.col(|ui| {
                         ui.label(STAT_ROWS[index].to_string());
                     });
-                    for player in player_stats.values() {
+                    for player in player_stats.iter() {
                         row.col(|ui| {
                             ui.label(STAT_ROWS[index].extract_formatted_stat(player).to_string());
                         });                    }